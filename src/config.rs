@@ -0,0 +1,209 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use toml;
+use error::Error;
+
+const CONFIG_FOLDER: &str = "rusty-sentry";
+const CONFIG_FILE: &str = "config.toml";
+const PLUGIN_CONFIG_FILE: &str = ".rusty-sentry.toml";
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthMethod {
+    Password,
+    Session,
+}
+
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Profile {
+    pub server: Option<String>,
+    pub auth: Option<AuthMethod>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub sid: Option<String>,
+    pub port: Option<u16>,
+    pub insecure: Option<bool>,
+    pub ca_bundle: Option<String>,
+    pub retries: Option<u32>,
+    pub retry_delay: Option<u64>,
+    pub keep_extensions: Option<bool>,
+    pub dry_run: Option<bool>,
+    pub check_conflicts: Option<bool>,
+    pub allow_delete: Option<bool>,
+    pub eval_dsl: Option<bool>,
+}
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+fn pick<T: Clone>(value: &Option<T>, fallback: &Option<T>) -> Option<T> {
+    value.clone().or_else(|| fallback.clone())
+}
+
+impl Profile {
+//    Values of `other` win over the values of this profile
+    fn merge(&self, other: &Profile) -> Profile {
+        Profile {
+            server: pick(&other.server, &self.server),
+            auth: pick(&other.auth, &self.auth),
+            username: pick(&other.username, &self.username),
+            password: pick(&other.password, &self.password),
+            sid: pick(&other.sid, &self.sid),
+            port: pick(&other.port, &self.port),
+            insecure: pick(&other.insecure, &self.insecure),
+            ca_bundle: pick(&other.ca_bundle, &self.ca_bundle),
+            retries: pick(&other.retries, &self.retries),
+            retry_delay: pick(&other.retry_delay, &self.retry_delay),
+            keep_extensions: pick(&other.keep_extensions, &self.keep_extensions),
+            dry_run: pick(&other.dry_run, &self.dry_run),
+            check_conflicts: pick(&other.check_conflicts, &self.check_conflicts),
+            allow_delete: pick(&other.allow_delete, &self.allow_delete),
+            eval_dsl: pick(&other.eval_dsl, &self.eval_dsl),
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Config, Error> {
+        match toml::from_str(contents) {
+            Ok(config) => Ok(config),
+            Err(e) => Err(Error::Config(format!("Cannot parse configuration: {}", e)))
+        }
+    }
+
+    pub fn read(path: &Path) -> Result<Config, Error> {
+        let mut f = File::open(path)?;
+        let mut contents = String::new();
+        f.read_to_string(&mut contents)?;
+        Config::parse(&contents)
+            .map_err(|e| Error::Config(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn global_path() -> Option<PathBuf> {
+        let config_home = match env::var("XDG_CONFIG_HOME") {
+            Ok(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+            _ => env::home_dir().map(|home| home.join(".config")),
+        };
+        config_home.map(|dir| dir.join(CONFIG_FOLDER).join(CONFIG_FILE))
+    }
+
+    pub fn load(plugin_folder: &Path) -> Result<Config, Error> {
+        let mut config = Config::default();
+        if let Some(path) = Config::global_path() {
+            if path.exists() {
+                config = config.merge(Config::read(&path)?);
+            }
+        }
+        let plugin_config = plugin_folder.join(PLUGIN_CONFIG_FILE);
+        if plugin_config.exists() {
+            config = config.merge(Config::read(&plugin_config)?);
+        }
+        Ok(config)
+    }
+
+    fn merge(mut self, other: Config) -> Config {
+        if other.default_profile.is_some() {
+            self.default_profile = other.default_profile;
+        }
+        for (name, profile) in other.profiles {
+            let merged = match self.profiles.get(&name) {
+                Some(existing) => existing.merge(&profile),
+                None => profile,
+            };
+            self.profiles.insert(name, merged);
+        }
+        self
+    }
+
+    pub fn profile(&self, name: Option<&str>) -> Result<Profile, Error> {
+        let name = match name {
+            Some(name) => name,
+            None => match self.default_profile {
+                Some(ref name) => name,
+                None => return Ok(Profile::default()),
+            }
+        };
+        match self.profiles.get(name) {
+            Some(profile) => Ok(profile.clone()),
+            None => Err(Error::Config(format!("Profile {} is not defined", name)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static GLOBAL: &str = r#"
+default_profile = "dev1"
+
+[profiles.dev1]
+server = "https://dev1:8443"
+auth = "password"
+username = "admin"
+password = "changeme"
+insecure = true
+
+[profiles.local]
+server = "http://localhost:8000"
+auth = "session"
+keep_extensions = true
+"#;
+
+    static PLUGIN: &str = r#"
+default_profile = "local"
+
+[profiles.dev1]
+keep_extensions = true
+"#;
+
+    #[test]
+    fn default_profile() {
+        let config = Config::parse(GLOBAL).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.server, Some(String::from("https://dev1:8443")));
+        assert_eq!(profile.auth, Some(AuthMethod::Password));
+        assert_eq!(profile.insecure, Some(true));
+        assert_eq!(profile.keep_extensions, None);
+    }
+
+    #[test]
+    fn named_profile() {
+        let config = Config::parse(GLOBAL).unwrap();
+        let profile = config.profile(Some("local")).unwrap();
+        assert_eq!(profile.server, Some(String::from("http://localhost:8000")));
+        assert_eq!(profile.auth, Some(AuthMethod::Session));
+        assert!(config.profile(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn plugin_config_overrides_global() {
+        let config = Config::parse(GLOBAL).unwrap().merge(Config::parse(PLUGIN).unwrap());
+        assert_eq!(config.default_profile, Some(String::from("local")));
+        let profile = config.profile(Some("dev1")).unwrap();
+        assert_eq!(profile.keep_extensions, Some(true));
+        assert_eq!(profile.username, Some(String::from("admin")));
+    }
+
+    #[test]
+    fn no_profiles() {
+        let config = Config::parse("").unwrap();
+        let profile = config.profile(None).unwrap();
+        assert!(profile.server.is_none());
+    }
+
+    #[test]
+    fn invalid_config() {
+        match Config::parse("[profiles.dev1]\nauth = \"kerberos\"\n") {
+            Err(Error::Config(_)) => {},
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,239 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edit {
+    Equal(usize, usize),
+    Delete(usize),
+    Insert(usize),
+}
+
+//    Lengths of the longest common subsequences of `old` and every prefix of `new`, in linear space
+fn lcs_row(old: &[&str], new: &[&str]) -> Vec<usize> {
+    let mut row = vec![0; new.len() + 1];
+    for line in old {
+        let mut next = vec![0; new.len() + 1];
+        for (j, other) in new.iter().enumerate() {
+            next[j + 1] = if line == other { row[j] + 1 } else { row[j + 1].max(next[j]) };
+        }
+        row = next;
+    }
+    row
+}
+
+//    Hirschberg's algorithm, `old` is split in half and `new` where the two halves share the most lines
+fn lcs_edits(old: &[&str], new: &[&str], i0: usize, j0: usize, result: &mut Vec<Edit>) {
+    if old.is_empty() {
+        result.extend((j0..j0 + new.len()).map(Edit::Insert));
+        return;
+    }
+    if new.is_empty() {
+        result.extend((i0..i0 + old.len()).map(Edit::Delete));
+        return;
+    }
+    if old.len() == 1 {
+        match new.iter().position(|line| *line == old[0]) {
+            Some(j) => {
+                result.extend((j0..j0 + j).map(Edit::Insert));
+                result.push(Edit::Equal(i0, j0 + j));
+                result.extend((j0 + j + 1..j0 + new.len()).map(Edit::Insert));
+            },
+            None => {
+                result.push(Edit::Delete(i0));
+                result.extend((j0..j0 + new.len()).map(Edit::Insert));
+            }
+        }
+        return;
+    }
+
+    let mid = old.len() / 2;
+    let forward = lcs_row(&old[..mid], new);
+    let old_rev: Vec<&str> = old[mid..].iter().rev().cloned().collect();
+    let new_rev: Vec<&str> = new.iter().rev().cloned().collect();
+    let backward = lcs_row(&old_rev, &new_rev);
+    let split = (0..new.len() + 1)
+        .max_by_key(|&k| (forward[k] + backward[new.len() - k], new.len() - k))
+        .unwrap_or(0);
+    lcs_edits(&old[..mid], &new[..split], i0, j0, result);
+    lcs_edits(&old[mid..], &new[split..], i0 + mid, j0 + split, result);
+}
+
+//    The common prefix and suffix are matched directly, usually only a few lines are left for the LCS
+fn edits(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new.iter()).take_while(|&(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|&(a, b)| a == b).count();
+    let mut result: Vec<Edit> = (0..prefix).map(|i| Edit::Equal(i, i)).collect();
+    lcs_edits(&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix], prefix, prefix, &mut result);
+    result.extend((0..suffix).map(|k| Edit::Equal(old.len() - suffix + k, new.len() - suffix + k)));
+    result
+}
+
+//    Line of `old` matching each line of `new`, changed lines are paired in order
+pub fn pair_lines(old: &[&str], new: &[&str]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut pairs = Vec::new();
+    let mut deleted = Vec::new();
+    let mut inserted = Vec::new();
+    for edit in edits(old, new).into_iter().chain(Some(Edit::Equal(old.len(), new.len()))) {
+        match edit {
+            Edit::Delete(i) => deleted.push(i),
+            Edit::Insert(j) => inserted.push(j),
+            Edit::Equal(i, j) => {
+                for k in 0..deleted.len().max(inserted.len()) {
+                    pairs.push((deleted.get(k).cloned(), inserted.get(k).cloned()));
+                }
+                deleted.clear();
+                inserted.clear();
+                if i < old.len() {
+                    pairs.push((Some(i), Some(j)));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+//    Lines keep their line ending, so a missing final newline or a CRLF ending is a difference
+fn split_lines(text: &str) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (index, _) in text.match_indices('\n') {
+        lines.push(&text[start..index + 1]);
+        start = index + 1;
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+fn push_line(out: &mut String, prefix: char, line: &str) {
+    out.push(prefix);
+    let content = if line.ends_with('\n') { &line[..line.len() - 1] } else { line };
+    if content.ends_with('\r') {
+        out.push_str(&content[..content.len() - 1]);
+        out.push_str("^M");
+    } else {
+        out.push_str(content);
+    }
+    out.push('\n');
+    if !line.ends_with('\n') {
+        out.push_str("\\ No newline at end of file\n");
+    }
+}
+
+fn range(start: usize, count: usize) -> String {
+//    An empty range points at the line before it
+    let start = if count == 0 { start } else { start + 1 };
+    if count == 1 {
+        format!("{}", start)
+    } else {
+        format!("{},{}", start, count)
+    }
+}
+
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str, context: usize) -> String {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let edits = edits(&old_lines, &new_lines);
+
+    let changes: Vec<usize> = edits.iter().enumerate()
+        .filter(|&(_, edit)| match *edit { Edit::Equal(..) => false, _ => true })
+        .map(|(index, _)| index)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+//    Changes closer than twice the context share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &change in changes.iter() {
+        let start = change.saturating_sub(context);
+        let end = (change + context + 1).min(edits.len());
+        match hunks.last_mut() {
+            Some(hunk) if start <= hunk.1 => hunk.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_name, new_name);
+    for (start, end) in hunks {
+        let old_start = edits[..start].iter().filter(|e| match **e { Edit::Insert(_) => false, _ => true }).count();
+        let new_start = edits[..start].iter().filter(|e| match **e { Edit::Delete(_) => false, _ => true }).count();
+        let mut old_count = 0;
+        let mut new_count = 0;
+        let mut body = String::new();
+        for edit in edits[start..end].iter() {
+            match *edit {
+                Edit::Equal(i, _) => {
+                    push_line(&mut body, ' ', old_lines[i]);
+                    old_count += 1;
+                    new_count += 1;
+                },
+                Edit::Delete(i) => {
+                    push_line(&mut body, '-', old_lines[i]);
+                    old_count += 1;
+                },
+                Edit::Insert(j) => {
+                    push_line(&mut body, '+', new_lines[j]);
+                    new_count += 1;
+                },
+            }
+        }
+        out.push_str(&format!("@@ -{} +{} @@\n", range(old_start, old_count), range(new_start, new_count)));
+        out.push_str(&body);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_content() {
+        assert_eq!(unified_diff("a\nb\n", "a\nb\n", "server", "local", 3), "");
+    }
+
+    #[test]
+    fn changed_line() {
+        let diff = unified_diff("a\nb\nc\n", "a\nB\nc\n", "server", "local", 3);
+        assert_eq!(diff, "--- server\n+++ local\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n");
+    }
+
+    #[test]
+    fn added_to_empty() {
+        let diff = unified_diff("", "a\nb\n", "server", "local", 3);
+        assert_eq!(diff, "--- server\n+++ local\n@@ -0,0 +1,2 @@\n+a\n+b\n");
+    }
+
+    #[test]
+    fn separate_hunks() {
+        let old: Vec<String> = (1..21).map(|i| i.to_string()).collect();
+        let mut new = old.clone();
+        new[1] = String::from("two");
+        new[17] = String::from("eighteen");
+        let diff = unified_diff(&old.join("\n"), &new.join("\n"), "server", "local", 2);
+        let headers: Vec<&str> = diff.lines().filter(|line| line.starts_with("@@")).collect();
+        assert_eq!(headers, vec!["@@ -1,4 +1,4 @@", "@@ -16,5 +16,5 @@"]);
+    }
+
+    #[test]
+    fn missing_newline() {
+        let diff = unified_diff("a\nb\n", "a\nb", "server", "local", 3);
+        assert_eq!(diff, "--- server\n+++ local\n@@ -1,2 +1,2 @@\n a\n-b\n+b\n\\ No newline at end of file\n");
+    }
+
+    #[test]
+    fn crlf_line() {
+        let diff = unified_diff("a\nb\n", "a\r\nb\n", "server", "local", 3);
+        assert_eq!(diff, "--- server\n+++ local\n@@ -1,2 +1,2 @@\n-a\n+a^M\n b\n");
+    }
+
+    #[test]
+    fn longest_common_lines() {
+        let old = vec!["a", "b", "c", "d", "e", "f", "g"];
+        let new = vec!["x", "b", "c", "y", "e", "g", "z"];
+        let common: Vec<(usize, usize)> = edits(&old, &new).into_iter()
+            .filter_map(|edit| match edit { Edit::Equal(i, j) => Some((i, j)), _ => None })
+            .collect();
+        assert_eq!(common, vec![(1, 1), (2, 2), (4, 4), (6, 5)]);
+        assert_eq!(edits(&old, &new).len(), old.len() + new.len() - common.len());
+    }
+}
@@ -0,0 +1,484 @@
+use regex::{Regex, Captures};
+use serde_json::{self, Value};
+use error::Error;
+use ef_client::Step;
+use server::FlowServer;
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StepDsl {
+    pub name: String,
+    pub command_file: Option<String>,
+    pub command: Option<String>,
+    pub shell: Option<String>,
+    pub condition: Option<String>,
+    pub timeout: Option<String>,
+    pub resource: Option<String>,
+    pub description: Option<String>,
+    pub post_processor: Option<String>,
+}
+
+fn or_empty(value: &Option<String>) -> Option<String> {
+    Some(value.clone().unwrap_or_default())
+}
+
+impl StepDsl {
+//    The DSL describes the whole step, so attributes missing from it are sent empty to clear them on the server
+    pub fn to_step(&self, file_command: Option<String>) -> Step {
+        Step {
+            step_name: self.name.clone(),
+            command: or_empty(&file_command.or_else(|| self.command.clone())),
+            shell: or_empty(&self.shell),
+            condition: or_empty(&self.condition),
+            time_limit: or_empty(&self.timeout),
+            resource_name: or_empty(&self.resource),
+            description: or_empty(&self.description),
+            post_processor: or_empty(&self.post_processor),
+        }
+    }
+}
+
+//    Both `step 'name', shell: 'ec-perl'` and `step 'name', { shell = 'ec-perl' }` are understood.
+//    Names are looked up in the outline so that text inside strings never matches, values are read from the code.
+fn attribute(code: &str, outline: &str, names: &[&str]) -> Option<String> {
+    for name in names {
+        let quoted = Regex::new(&format!("\\b{}\\s*[:=]\\s*('''|\"\"\"|'|\")", name)).unwrap();
+        if let Some(caps) = quoted.captures(outline) {
+            let delimiter = caps.get(1).unwrap();
+            let start = delimiter.end();
+            let end = outline[start..].find(delimiter.as_str()).map_or(outline.len(), |p| start + p);
+            return Some(String::from(&code[start..end]));
+        }
+        let number = Regex::new(&format!("\\b{}\\s*[:=]\\s*(\\d+)", name)).unwrap();
+        if let Some(caps) = number.captures(outline) {
+            return Some(String::from(caps.get(1).unwrap().as_str()));
+        }
+    }
+    None
+}
+
+const PLUGIN_FILE: &str = "new\\s+File\\s*\\(\\s*pluginDir\\s*,\\s*(?:'([^']*)'|\"([^\"]*)\")\\s*\\)";
+
+fn file_name(caps: &Captures) -> String {
+    String::from(caps.get(1).or(caps.get(2)).map_or("", |m| m.as_str()))
+}
+
+fn command_file(code: &str, outline: &str) -> Option<String> {
+    let re = Regex::new(PLUGIN_FILE).unwrap();
+    re.captures_iter(code)
+        .find(|caps| outline[caps.get(0).unwrap().start()..].starts_with("new"))
+        .map(|caps| file_name(&caps))
+}
+
+//    Blanks the comments in the code, and the string contents as well in the outline, keeping every offset
+fn blank(contents: &str) -> (String, String) {
+    let bytes = contents.as_bytes();
+    let mut code = bytes.to_vec();
+    let mut outline = bytes.to_vec();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(b"//") || bytes[i..].starts_with(b"/*") {
+            let end = if bytes[i + 1] == b'/' {
+                bytes[i..].iter().position(|&b| b == b'\n').map_or(bytes.len(), |p| i + p)
+            } else {
+                contents[i + 2..].find("*/").map_or(bytes.len(), |p| i + 2 + p + 2)
+            };
+            for j in i..end {
+                if bytes[j] != b'\n' {
+                    code[j] = b' ';
+                    outline[j] = b' ';
+                }
+            }
+            i = end;
+        } else if bytes[i] == b'\'' || bytes[i] == b'"' {
+            let quote = bytes[i];
+            let delimiter = if bytes[i..].starts_with(&[quote, quote, quote]) { vec![quote; 3] } else { vec![quote] };
+            i += delimiter.len();
+            while i < bytes.len() && !bytes[i..].starts_with(&delimiter) {
+                let mut length = if bytes[i] == b'\\' { 2 } else { 1 };
+//                Whole characters are blanked, so the offsets stay valid in the lossy conversion
+                while i + length < bytes.len() && bytes[i + length] & 0xC0 == 0x80 {
+                    length += 1;
+                }
+                for j in i..(i + length).min(bytes.len()) {
+                    if bytes[j] != b'\n' {
+                        outline[j] = b' ';
+                    }
+                }
+                i += length;
+            }
+            i += delimiter.len();
+        } else {
+            i += 1;
+        }
+    }
+    (String::from_utf8_lossy(&code).into_owned(), String::from_utf8_lossy(&outline).into_owned())
+}
+
+fn line_of(contents: &str, offset: usize) -> usize {
+    contents[..offset].matches('\n').count() + 1
+}
+
+//    The end of a step declaration: its closing brace, the end of the statement, or the end of the enclosing block
+fn declaration_end(outline: &str, start: usize) -> usize {
+    let mut depth = 0;
+    let mut continued = true;
+    for (offset, c) in outline[start..].char_indices() {
+        match c {
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' if depth == 0 => return start + offset,
+            '}' if depth == 1 => return start + offset + 1,
+            '}' | ')' | ']' => depth -= 1,
+            ';' if depth == 0 => return start + offset,
+            '\n' if depth == 0 && !continued => return start + offset,
+            _ => {}
+        }
+//        Named arguments go on over several lines as long as a line ends with a comma
+        if !c.is_whitespace() {
+            continued = c == ',';
+        }
+    }
+    outline.len()
+}
+
+//    Steps in the order they are declared, each one described by its own declaration.
+//    A step the name of which is not a literal cannot be told apart from the others, so it is an error.
+pub fn parse_steps(contents: &str) -> Result<Vec<StepDsl>, Error> {
+    let (code, outline) = blank(contents);
+    let declaration = Regex::new("\\bstep\\b").unwrap();
+    let literal = Regex::new("^step\\s*\\(?\\s*(?:'([^']+)'|\"([^\"$]+)\")").unwrap();
+    let mut starts: Vec<(usize, String)> = Vec::new();
+    for found in declaration.find_iter(&outline) {
+        match literal.captures(&code[found.start()..]) {
+            Some(caps) => {
+                let name = caps.get(1).or(caps.get(2)).unwrap().as_str();
+                if starts.iter().any(|s| s.1 == name) {
+                    return Err(Error::Mapping(format!("Step {} is declared twice, the second time at line {}",
+                                                      name, line_of(contents, found.start()))));
+                }
+                starts.push((found.start(), String::from(name)));
+            },
+            None => {
+                return Err(Error::Mapping(format!("Cannot read the step name at line {}, steps must be named with a string literal",
+                                                  line_of(contents, found.start()))));
+            }
+        }
+    }
+
+    let mut steps = Vec::new();
+    for &(start, ref name) in starts.iter() {
+        let start = start + "step".len();
+        let end = declaration_end(&outline, start);
+        let (code, outline) = (&code[start..end], &outline[start..end]);
+        steps.push(StepDsl {
+            name: name.clone(),
+            command_file: command_file(code, outline),
+            command: attribute(code, outline, &["command"]),
+            shell: attribute(code, outline, &["shell"]),
+            condition: attribute(code, outline, &["condition"]),
+            timeout: attribute(code, outline, &["timeLimit", "timeout"]),
+            resource: attribute(code, outline, &["resourceName", "resource"]),
+            description: attribute(code, outline, &["description"]),
+            post_processor: attribute(code, outline, &["postProcessor"]),
+        });
+    }
+    Ok(steps)
+}
+
+//    An attribute the server does not return is the same as an empty one
+fn differs(current: &Step, step: &Step) -> bool {
+    let attributes = [
+        (&step.command, &current.command),
+        (&step.shell, &current.shell),
+        (&step.condition, &current.condition),
+        (&step.time_limit, &current.time_limit),
+        (&step.resource_name, &current.resource_name),
+        (&step.description, &current.description),
+        (&step.post_processor, &current.post_processor),
+    ];
+    attributes.iter().any(|&(wanted, actual)| {
+        wanted.as_ref().map_or("", |v| v.as_str()) != actual.as_ref().map_or("", |v| v.as_str())
+    })
+}
+
+//    Creates, updates, deletes and reorders the steps of the procedure to match the DSL.
+//    Steps missing from the DSL are kept after the others unless deleting is allowed.
+pub fn reconcile<S: FlowServer>(server: &S, project: &str, procedure: &str, steps: &[Step], allow_delete: bool) -> Result<(), Error> {
+    let existing = server.get_steps(project, procedure)?;
+    let mut order = Vec::new();
+    for current in existing.iter() {
+        if steps.iter().any(|s| s.step_name == current.step_name) {
+            order.push(current.step_name.clone());
+        } else if allow_delete {
+            println!("Deleting step {} of procedure {}", current.step_name, procedure);
+            server.delete_step(project, procedure, &current.step_name)?;
+        } else {
+            println!("Keeping step {} of procedure {} on the server, use --allow-delete to delete it", current.step_name, procedure);
+            order.push(current.step_name.clone());
+        }
+    }
+    for step in steps {
+        match existing.iter().find(|s| s.step_name == step.step_name) {
+            None => {
+                println!("Creating step {} of procedure {}", step.step_name, procedure);
+                server.create_step(project, procedure, step)?;
+                order.push(step.step_name.clone());
+            },
+            Some(current) if differs(current, step) => {
+                println!("Updating step {} of procedure {}", step.step_name, procedure);
+                server.modify_step(project, procedure, step)?;
+            },
+            Some(_) => {}
+        }
+    }
+    for (index, step) in steps.iter().enumerate() {
+        if order[index] == step.step_name {
+            continue;
+        }
+        println!("Moving step {} of procedure {} before {}", step.step_name, procedure, order[index]);
+        server.move_step(project, procedure, &step.step_name, Some(&order[index]))?;
+        let position = order.iter().position(|name| *name == step.step_name).unwrap();
+        let name = order.remove(position);
+        order.insert(index, name);
+    }
+    Ok(())
+}
+
+pub struct Script {
+    pub text: String,
+    prelude_line: usize,
+}
+
+//    Single quoted, so that nothing in the file is interpolated, and on one line to keep line numbers
+fn groovy_string(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n");
+    format!("'{}'", escaped)
+}
+
+//    The server only has the installed copy of the plugin, so files read from pluginDir are inlined.
+//    The script runs in the plugin project, set up on one line after the imports.
+pub fn script<F>(contents: &str, plugin_key: &str, project: &str, read: F) -> Result<Script, Error>
+    where F: Fn(&str) -> Result<String, Error> {
+    let re = Regex::new(&format!("{}\\s*\\.\\s*text\\b", PLUGIN_FILE)).unwrap();
+    let mut inlined = String::new();
+    let mut last = 0;
+    for caps in re.captures_iter(contents) {
+        let whole = caps.get(0).unwrap();
+        inlined.push_str(&contents[last..whole.start()]);
+        inlined.push_str(&groovy_string(&read(&file_name(&caps))?));
+        last = whole.end();
+    }
+    inlined.push_str(&contents[last..]);
+
+    let lines: Vec<&str> = inlined.lines().collect();
+    let header = lines.iter().take_while(|line| {
+        let line = line.trim();
+        line.is_empty() || line.starts_with("import ") || line.starts_with("//")
+    }).count();
+    let has_project = Regex::new("(?m)^\\s*project\\s").unwrap().is_match(&inlined);
+    let mut prelude = format!("def pluginKey = {}; def pluginName = {}; def projectName = pluginName;",
+                              groovy_string(plugin_key), groovy_string(project));
+    if !has_project {
+        prelude.push_str(" project projectName, {");
+    }
+
+    let mut text = lines[..header].join("\n");
+    if header > 0 {
+        text.push('\n');
+    }
+    text.push_str(&prelude);
+    text.push('\n');
+    text.push_str(&lines[header..].join("\n"));
+    if !has_project {
+        text.push_str("\n}");
+    }
+    Ok(Script { text, prelude_line: header + 1 })
+}
+
+fn script_line(message: &str) -> Option<usize> {
+    let patterns = ["(?i)\\bline:?\\s*(\\d+)", "\\.groovy:\\s*(\\d+)"];
+    patterns.iter()
+        .filter_map(|pattern| Regex::new(pattern).unwrap().captures(message))
+        .next()
+        .and_then(|caps| caps.get(1).unwrap().as_str().parse().ok())
+}
+
+//    Points the evaluation errors of the server at the line of the local file
+pub fn script_error(file: &str, script: &Script, error: Error) -> Error {
+    let body = match error {
+        Error::Http { ref body, .. } => body.clone(),
+        _ => return error,
+    };
+    let message = serde_json::from_str::<Value>(&body).ok()
+        .and_then(|value| value["error"]["message"].as_str().map(String::from))
+        .unwrap_or(body);
+    let line = script_line(&message).map(|line| if line > script.prelude_line { line - 1 } else { line });
+    Error::Dsl { file: String::from(file), line, message }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use server::{Call, RecordingServer};
+
+    static PROCEDURE: &str = r#"
+def procName = 'Deploy'
+procedure procName, description: 'Deploys the application', {
+
+    step 'setup',
+        command: new File(pluginDir, "dsl/procedures/Deploy/steps/setup.pl").text,
+        shell: 'ec-perl',
+        timeLimit: 30
+
+    step "deploy application", {
+        description = 'Runs the deployment'
+        command = new File(pluginDir, 'dsl/procedures/Deploy/steps/deploy.groovy').text
+        shell = 'ec-groovy'
+        condition = '$[/myJob/deploy]'
+        resourceName = 'local'
+    }
+}
+"#;
+
+    #[test]
+    fn parse_procedure_steps() {
+        let steps = parse_steps(PROCEDURE).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert_eq!(steps[0], StepDsl {
+            name: String::from("setup"),
+            command_file: Some(String::from("dsl/procedures/Deploy/steps/setup.pl")),
+            shell: Some(String::from("ec-perl")),
+            timeout: Some(String::from("30")),
+            ..StepDsl::default()
+        });
+        assert_eq!(steps[1].name, "deploy application");
+        assert_eq!(steps[1].command_file, Some(String::from("dsl/procedures/Deploy/steps/deploy.groovy")));
+        assert_eq!(steps[1].condition, Some(String::from("$[/myJob/deploy]")));
+        assert_eq!(steps[1].resource, Some(String::from("local")));
+        assert_eq!(steps[1].description, Some(String::from("Runs the deployment")));
+    }
+
+    #[test]
+    fn no_steps() {
+        assert!(parse_steps("procedure 'Empty', {\n}\n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn steps_in_comments_and_strings() {
+        let contents = "procedure 'Deploy', description: 'one step', {\n// step 'old'\n/* step \"older\" */\n    step 'run', command: 'echo // step'\n}\n";
+        let steps = parse_steps(contents).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].name, "run");
+        match parse_steps("procedure 'Deploy', {\n    names.each { name ->\n        step name, shell: 'ec-perl'\n    }\n}\n") {
+            Err(Error::Mapping(message)) => assert!(message.contains("line 3")),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn step_ends_with_its_declaration() {
+        let contents = "procedure 'Deploy', {\n    step 'setup', shell: 'ec-perl',\n        command: 'echo \"shell: bash\"'\n    step('run') {\n        condition = 'true'\n    }\n    description = 'Deploys'\n    timeLimit: 10\n}\nproperty 'resource', value: 'local'\n";
+        let steps = parse_steps(contents).unwrap();
+        assert_eq!(steps[0].shell, Some(String::from("ec-perl")));
+        assert_eq!(steps[0].command, Some(String::from("echo \"shell: bash\"")));
+        assert_eq!((steps[0].description.clone(), steps[0].timeout.clone()), (None, None));
+        assert_eq!(steps[1], StepDsl {
+            name: String::from("run"),
+            condition: Some(String::from("true")),
+            ..StepDsl::default()
+        });
+    }
+
+    fn step(name: &str, command: &str) -> Step {
+        Step {
+            step_name: String::from(name),
+            command: Some(String::from(command)),
+            ..Step::default()
+        }
+    }
+
+    #[test]
+    fn inline_command_and_cleared_attributes() {
+        let steps = parse_steps("procedure 'Deploy', {\n    step 'hello', command: 'echo hi'\n}\n").unwrap();
+        let step = steps[0].to_step(None);
+        assert_eq!(step.command, Some(String::from("echo hi")));
+        assert_eq!(step.condition, Some(String::new()));
+
+        let mut current = step.clone();
+        current.condition = Some(String::from("$[/myJob/deploy]"));
+        assert!(differs(&current, &step));
+        current.condition = None;
+        assert!(!differs(&current, &step));
+    }
+
+    #[test]
+    fn duplicate_steps() {
+        let contents = "procedure 'Deploy', {\n    step 'setup', shell: 'ec-perl'\n    step \"setup\", shell: 'ec-groovy'\n}\n";
+        assert!(parse_steps(contents).is_err());
+    }
+
+    #[test]
+    fn reconcile_steps() {
+        let server = RecordingServer::new();
+        for name in ["first", "obsolete", "second"].iter() {
+            server.create_step("EC-Test-1.0.0", "Deploy", &step(name, "echo")).unwrap();
+        }
+        let wanted = vec![step("second", "echo"), step("new", "echo new"), step("first", "echo first")];
+        reconcile(&server, "EC-Test-1.0.0", "Deploy", &wanted, true).unwrap();
+
+        let steps = server.steps("EC-Test-1.0.0", "Deploy");
+        assert_eq!(steps, wanted);
+        assert!(server.calls().contains(&Call::DeleteStep {
+            project: String::from("EC-Test-1.0.0"),
+            procedure: String::from("Deploy"),
+            step: String::from("obsolete"),
+        }));
+
+        let calls = server.calls().len();
+        reconcile(&server, "EC-Test-1.0.0", "Deploy", &wanted, true).unwrap();
+        assert_eq!(server.calls().len(), calls);
+    }
+
+    #[test]
+    fn steps_are_kept_without_allow_delete() {
+        let server = RecordingServer::new();
+        for name in ["obsolete", "first"].iter() {
+            server.create_step("EC-Test-1.0.0", "Deploy", &step(name, "echo")).unwrap();
+        }
+        let wanted = vec![step("first", "echo")];
+        reconcile(&server, "EC-Test-1.0.0", "Deploy", &wanted, false).unwrap();
+        let names: Vec<String> = server.steps("EC-Test-1.0.0", "Deploy").into_iter().map(|s| s.step_name).collect();
+        assert_eq!(names, vec!["first", "obsolete"]);
+    }
+
+    #[test]
+    fn script_in_project_context() {
+        let contents = "import java.io.File\n\nprocedure 'Deploy', {\n    step 'setup', command: new File(pluginDir, 'steps/setup.pl').text\n}\n";
+        let script = script(contents, "EC-Test", "EC-Test-1.0.0", |file| {
+            assert_eq!(file, "steps/setup.pl");
+            Ok(String::from("print 'it\\'s';\nexit 0;"))
+        }).unwrap();
+        let lines: Vec<&str> = script.text.lines().collect();
+        assert_eq!(lines[0], "import java.io.File");
+        assert_eq!(lines[2], "def pluginKey = 'EC-Test'; def pluginName = 'EC-Test-1.0.0'; def projectName = pluginName; project projectName, {");
+        assert_eq!(lines[4], "    step 'setup', command: 'print \\'it\\\\\\'s\\';\\nexit 0;'");
+        assert_eq!(lines.last(), Some(&"}"));
+
+        let error = Error::Http {
+            status: 400,
+            body: String::from(r#"{"error": {"code": "InvalidScript", "message": "startup failed: Script1.groovy: 6: unexpected token @ line 6, column 5."}}"#),
+        };
+        let error = script_error("dsl/procedures/Deploy/procedure.dsl", &script, error);
+        assert_eq!(error.to_string(), "dsl/procedures/Deploy/procedure.dsl:5: startup failed: Script1.groovy: 6: unexpected token @ line 6, column 5.");
+    }
+
+    #[test]
+    fn script_with_project() {
+        let script = script("project pluginName, {\n}\n", "EC-Test", "EC-Test-1.0.0", |_| unreachable!()).unwrap();
+        assert!(!script.text.contains("project projectName"));
+        assert_eq!(script_line("Error evaluating DSL at line: 3"), Some(3));
+        assert_eq!(script_line("No such property: foo"), None);
+    }
+}
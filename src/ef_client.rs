@@ -1,34 +1,184 @@
-use reqwest::{ClientBuilder, Method, Client};
-use reqwest::header::{Headers, ContentType, Cookie};
-use std::io::{Error,ErrorKind};
+use reqwest::{ClientBuilder, Method, Client, StatusCode, Certificate};
+use reqwest::header::{CONTENT_TYPE, COOKIE};
+use error::Error;
+use std::io;
 use std::io::Read;
+use std::fs::File;
+use std::path::PathBuf;
 use std::collections::HashMap;
+use std::cell::RefCell;
+use std::thread;
+use std::time::Duration;
+use url::Url;
 use url::percent_encoding::{utf8_percent_encode, DEFAULT_ENCODE_SET};
+use url::form_urlencoded;
 use serde_json;
 
-const PORT: &str = "443";
+const PROTOCOL: &str = "https";
+const RETRIES: u32 = 3;
+const RETRY_DELAY_MS: u64 = 500;
+const PEM_CERTIFICATE_END: &str = "-----END CERTIFICATE-----";
+
+//    Names go into the path one segment each, so slashes, spaces, '?' and '#' in them are encoded
+fn segment(name: &str) -> String {
+    form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>().replace('+', "%20")
+}
 
 #[derive(Debug, Clone)]
 pub struct EFClient {
-    server: String,
+    url: Url,
     username: Option<String>,
     password: Option<String>,
-    sid: Option<String>,
-    port: String,
+    sid: RefCell<Option<String>>,
     client: Client,
+    retries: u32,
+    retry_delay: Duration,
     debug_level: i8
 }
 
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    pub insecure: bool,
+    pub ca_bundle: Option<PathBuf>,
+}
+
 #[derive(Deserialize, Debug)]
 struct PropertyResponse {
     property: Property
 }
 
-#[derive(Deserialize, Debug, Serialize)]
+#[derive(Deserialize, Debug, Serialize, Clone)]
 pub struct Property {
     #[serde(rename="propertyName")]
-    property_name: Option<String>,
-    value: String,
+    pub property_name: Option<String>,
+    pub value: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct LoginResponse {
+    #[serde(rename="sessionId")]
+    session_id: String
+}
+
+#[derive(Deserialize, Debug)]
+struct ServerStatusResponse {
+    #[serde(rename="serverStatus")]
+    server_status: ServerStatus
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ServerStatus {
+    #[serde(rename="serverState")]
+    pub server_state: String,
+    #[serde(rename="lastMessage", default)]
+    pub last_message: String
+}
+
+#[derive(Deserialize, Debug)]
+struct StepResponse {
+    step: Step
+}
+
+#[derive(Deserialize, Debug)]
+struct StepsResponse {
+    #[serde(default)]
+    step: Vec<Step>
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Step {
+    #[serde(rename="stepName")]
+    pub step_name: String,
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default)]
+    pub shell: Option<String>,
+    #[serde(default)]
+    pub condition: Option<String>,
+    #[serde(rename="timeLimit", default)]
+    pub time_limit: Option<String>,
+    #[serde(rename="resourceName", default)]
+    pub resource_name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(rename="postProcessor", default)]
+    pub post_processor: Option<String>,
+}
+
+impl Step {
+    pub fn attribute(&self, name: &str) -> Option<&Option<String>> {
+        match name {
+            "command" => Some(&self.command),
+            "shell" => Some(&self.shell),
+            "condition" => Some(&self.condition),
+            "timeLimit" => Some(&self.time_limit),
+            "resourceName" => Some(&self.resource_name),
+            "description" => Some(&self.description),
+            "postProcessor" => Some(&self.post_processor),
+            _ => None
+        }
+    }
+
+    pub fn attribute_mut(&mut self, name: &str) -> Option<&mut Option<String>> {
+        match name {
+            "command" => Some(&mut self.command),
+            "shell" => Some(&mut self.shell),
+            "condition" => Some(&mut self.condition),
+            "timeLimit" => Some(&mut self.time_limit),
+            "resourceName" => Some(&mut self.resource_name),
+            "description" => Some(&mut self.description),
+            "postProcessor" => Some(&mut self.post_processor),
+            _ => None
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct FormalParameterResponse {
+    #[serde(rename="formalParameter")]
+    formal_parameter: FormalParameter
+}
+
+#[derive(Deserialize, Debug)]
+struct FormalParametersResponse {
+    #[serde(rename="formalParameter", default)]
+    formal_parameters: Vec<FormalParameter>
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FormalParameter {
+    #[serde(rename="formalParameterName")]
+    pub name: String,
+    #[serde(rename="defaultValue", default)]
+    pub default_value: Option<String>,
+    #[serde(default)]
+    pub required: Option<String>,
+    #[serde(rename="type", default)]
+    pub parameter_type: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl FormalParameter {
+    pub fn attribute(&self, name: &str) -> Option<&Option<String>> {
+        match name {
+            "defaultValue" => Some(&self.default_value),
+            "required" => Some(&self.required),
+            "type" => Some(&self.parameter_type),
+            "description" => Some(&self.description),
+            _ => None
+        }
+    }
+
+    pub fn attribute_mut(&mut self, name: &str) -> Option<&mut Option<String>> {
+        match name {
+            "defaultValue" => Some(&mut self.default_value),
+            "required" => Some(&mut self.required),
+            "type" => Some(&mut self.parameter_type),
+            "description" => Some(&mut self.description),
+            _ => None
+        }
+    }
 }
 
 #[derive(Deserialize, Debug) ]
@@ -36,7 +186,7 @@ struct PluginResponse {
     plugin: Plugin
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 pub struct Plugin {
     #[serde(rename="pluginName")]
     pub plugin_name: String,
@@ -46,27 +196,89 @@ pub struct Plugin {
 
 impl EFClient {
     pub fn new(server: &str, username: Option<&str>, password: Option<&str>, sid: Option<&str>) -> Result<EFClient, Error> {
-        let client = match ClientBuilder::new()
-            .danger_disable_certificate_validation_entirely()
-            .build() {
-            Ok(c) => Ok(c),
-            Err(e) => Err(Error::new(ErrorKind::Other, format!("Cannot create client: {}", e)))
-        }?;
+        let url = Self::parse_server_url(server)?;
+        let client = Self::build_client(&TlsOptions::default())?;
 
         if sid == None && (username == None || password == None) {
-            return Err(Error::new(ErrorKind::Other, "Either username & password or sid must be provided"));
+            return Err(Error::Auth(String::from("Either username & password or sid must be provided")));
         }
         Ok(EFClient {
-            server: String::from(server),
+            url,
             username: username.map(str::to_string),
             password: password.map(str::to_string),
-            sid: sid.map(str::to_string),
+            sid: RefCell::new(sid.map(str::to_string)),
             client,
-            port: String::from(PORT),
+            retries: RETRIES,
+            retry_delay: Duration::from_millis(RETRY_DELAY_MS),
             debug_level: 0
         })
     }
 
+    fn parse_server_url(server: &str) -> Result<Url, Error> {
+//        A bare host name (optionally with a port) means https
+        let server = if server.contains("://") {
+            String::from(server)
+        } else {
+            format!("{}://{}", PROTOCOL, server)
+        };
+        let mut url = match Url::parse(&server) {
+            Ok(url) => url,
+            Err(e) => return Err(Error::Config(format!("Invalid server url {}: {}", server, e)))
+        };
+        if url.host_str().is_none() {
+            return Err(Error::Config(format!("Server url {} has no host", server)));
+        }
+        url.set_query(None);
+        url.set_fragment(None);
+        if !url.path().ends_with("/") {
+            let path = format!("{}/", url.path());
+            url.set_path(&path);
+        }
+        Ok(url)
+    }
+
+    fn build_client(tls: &TlsOptions) -> Result<Client, Error> {
+        let mut builder = ClientBuilder::new();
+        if let Some(ref path) = tls.ca_bundle {
+            for certificate in Self::read_ca_bundle(path)? {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        if tls.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        match builder.build() {
+            Ok(c) => Ok(c),
+            Err(e) => Err(Error::Transport(format!("Cannot create client: {}", e)))
+        }
+    }
+
+    fn read_ca_bundle(path: &PathBuf) -> Result<Vec<Certificate>, Error> {
+        let mut contents = String::new();
+        match File::open(path) {
+            Ok(mut f) => f.read_to_string(&mut contents)?,
+            Err(e) => return Err(Error::Io(io::Error::new(e.kind(), format!("Cannot open CA bundle {}: {}", path.display(), e))))
+        };
+//        A bundle holds several certificates, but each one has to be parsed separately
+        let mut certificates = Vec::new();
+        for pem in contents.split_terminator(PEM_CERTIFICATE_END).filter(|pem| pem.contains("-----BEGIN")) {
+            let pem = format!("{}{}\n", pem.trim_start(), PEM_CERTIFICATE_END);
+            match Certificate::from_pem(pem.as_bytes()) {
+                Ok(certificate) => certificates.push(certificate),
+                Err(e) => return Err(Error::Transport(format!("Cannot parse certificate from {}: {}", path.display(), e)))
+            }
+        }
+        if certificates.is_empty() {
+            return Err(Error::Transport(format!("No certificates found in {}", path.display())));
+        }
+        Ok(certificates)
+    }
+
+    pub fn set_tls(&mut self, tls: &TlsOptions) -> Result<(), Error> {
+        self.client = Self::build_client(tls)?;
+        Ok(())
+    }
+
     fn debug(&self, message: &str) {
         if self.debug_level > 0 {
             println!("[DEBUG] {}", message);
@@ -77,50 +289,121 @@ impl EFClient {
         self.debug_level = level;
     }
 
-    pub fn set_port(&mut self, port: &str) {
-        self.port = String::from(port);
+    pub fn set_port(&mut self, port: &str) -> Result<(), Error> {
+        let number = port.trim().parse::<u16>()
+            .map_err(|e| Error::Config(format!("Invalid port {}: {}", port, e)))?;
+        self.url.set_port(Some(number))
+            .map_err(|_| Error::Config(format!("Cannot set port {} for {}", port, self.url)))
+    }
+
+    pub fn base_url(&self) -> String {
+        self.url.to_string()
+    }
+
+    pub fn host(&self) -> String {
+        String::from(self.url.host_str().unwrap_or_default())
+    }
+
+    pub fn set_retries(&mut self, retries: u32, delay: Duration) {
+        self.retries = retries;
+        self.retry_delay = delay;
+    }
+
+    fn is_retryable(error: &Error) -> bool {
+        match *error {
+            Error::Transport(_) => true,
+            Error::Http { status, .. } => status == 502 || status == 503 || status == 504,
+            _ => false
+        }
+    }
+
+    pub fn has_credentials(&self) -> bool {
+        self.username != None && self.password != None
+    }
+
+    pub fn session_id(&self) -> Option<String> {
+        self.sid.borrow().clone()
     }
 
+    pub fn login(&self) -> Result<String, Error> {
+        if !self.has_credentials() {
+            return Err(Error::Auth(String::from("Username and password are required to log in")));
+        }
+        let username = self.username.clone().unwrap();
+        let password = self.password.clone().unwrap();
+        let mut payload = HashMap::new();
+        payload.insert("userName", username.as_str());
+        payload.insert("password", password.as_str());
+        self.debug(&format!("Logging in as {}", username));
+        let res = self.request_with_retries("sessions", Method::POST, Some(&payload), false)?;
+        let session: LoginResponse = serde_json::from_str(&res)?;
+        *self.sid.borrow_mut() = Some(session.session_id.clone());
+        Ok(session.session_id)
+    }
 
     fn request_json<'a>(&self, uri: &'a str, method: Method, payload: Option<&'a HashMap<&str, &str>>) -> Result<String, Error> {
-        let url= format!("https://{}:{}/rest/v1.0/{}", &self.server, &self.port, uri);
+        if self.sid.borrow().is_none() {
+            self.login()?;
+        }
+        match self.request_with_retries(uri, method.clone(), payload, true) {
+            Err(Error::Auth(_)) if self.has_credentials() => {
+                self.debug("Session has expired, logging in again");
+                self.login()?;
+                self.request_with_retries(uri, method, payload, true)
+            },
+            result => result
+        }
+    }
+
+    fn request_with_retries<'a>(&self, uri: &'a str, method: Method, payload: Option<&'a HashMap<&str, &str>>, authenticate: bool) -> Result<String, Error> {
+//        Only requests that can be safely repeated are retried
+        let idempotent = method == Method::GET || method == Method::PUT || method == Method::DELETE;
+        let mut attempt = 0;
+        loop {
+            match self.send_request(uri, method.clone(), payload, authenticate) {
+                Err(ref e) if idempotent && attempt < self.retries && Self::is_retryable(e) => {
+                    let delay = self.retry_delay * 2u32.pow(attempt);
+                    eprintln!("Request to {} failed: {}, retrying in {:?}", uri, e, delay);
+                    thread::sleep(delay);
+                    attempt += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+
+    fn send_request<'a>(&self, uri: &'a str, method: Method, payload: Option<&'a HashMap<&str, &str>>, authenticate: bool) -> Result<String, Error> {
+        let url= format!("{}rest/v1.0/{}", &self.url, uri);
         let mut req = self.client.request(method, &url);
 
-        let mut headers = Headers::new();
 //        Auth
-        if self.username != None && self.password != None {
-            let username = self.username.clone().unwrap();
-            req.basic_auth(
-                username,
-                self.password.clone()
-            );
-        }
-        else {
-            let mut cookie = Cookie::new();
-            let sid = self.sid.clone().expect("either username & password or sid must be provided");
-            cookie.append("sessionId", sid);
-            headers.set(cookie);
+        if authenticate {
+            if let Some(sid) = self.session_id() {
+                req = req.header(COOKIE, format!("sessionId={}", sid));
+            }
         }
 
-        headers.set(ContentType::json());
-        req.headers(headers);
+        req = req.header(CONTENT_TYPE, "application/json");
         match payload {
             Some(body) => {
                 self.debug(&format!("Body: {:?}", body));
-                req.json(&body.clone());
+                req = req.json(body);
             },
             None => {},
         };
-        let mut res = req.send().expect("Error happened while sending a request");
+        let mut res = match req.send() {
+            Ok(res) => res,
+            Err(e) => return Err(Error::Transport(format!("Cannot send request to {}: {}", url, e)))
+        };
+        let body = res.text()?;
         if res.status().is_success() {
-            let mut body: String = String::new();
-            let _ = res.read_to_string(&mut body)?;
             return Ok(body);
         }
         else {
-            let mut body: String = String::new();
-            res.read_to_string(&mut body)?;
-            return Err(Error::new(ErrorKind::Other, format!("Request failed: status code {}, body: {}", res.status(), body)))
+            if res.status() == StatusCode::UNAUTHORIZED {
+                return Err(Error::Auth(body));
+            }
+            return Err(Error::Http { status: res.status().as_u16(), body })
         }
     }
 
@@ -128,23 +411,169 @@ impl EFClient {
         let uri = format!("properties/{}", utf8_percent_encode(name, DEFAULT_ENCODE_SET).to_string());
         let mut payload = HashMap::new();
         payload.insert("value", value);
-        let res = &self.request_json(&uri, Method::Put, Some(&payload))?;
+        let res = &self.request_json(&uri, Method::PUT, Some(&payload))?;
         self.debug(&format!("API Response: {:?}", res));
         let property: PropertyResponse = serde_json::from_str(&res)?;
         Ok(property.property)
     }
 
+    pub fn delete_property(&self, name: &str) -> Result<(), Error> {
+        let uri = format!("properties/{}", utf8_percent_encode(name, DEFAULT_ENCODE_SET).to_string());
+        let _res = &self.request_json(&uri, Method::DELETE, None)?;
+        Ok(())
+    }
+
     pub fn set_procedure_command(&self, project_name: &str, procedure_name: &str, step_name: &str, command: &str) -> Result<(), Error> {
-        let uri = format!("projects/{}/procedures/{}/steps/{}", project_name, procedure_name, step_name);
+        self.set_step_attribute(project_name, procedure_name, step_name, "command", command)
+    }
+
+    pub fn get_procedure_command(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<String, Error> {
+        let step = self.get_step(project_name, procedure_name, step_name)?;
+        Ok(step.command.unwrap_or_default())
+    }
+
+    pub fn get_step(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<Step, Error> {
+        let uri = format!("projects/{}/procedures/{}/steps/{}", segment(project_name), segment(procedure_name), segment(step_name));
+        let res = &self.request_json(&uri, Method::GET, None)?;
+        let step: StepResponse = serde_json::from_str(&res)?;
+        Ok(step.step)
+    }
+
+    pub fn set_step_attribute(&self, project_name: &str, procedure_name: &str, step_name: &str, attribute: &str, value: &str) -> Result<(), Error> {
+        let uri = format!("projects/{}/procedures/{}/steps/{}", segment(project_name), segment(procedure_name), segment(step_name));
+        let mut payload = HashMap::new();
+        payload.insert(attribute, value);
+        let _res = &self.request_json(&uri, Method::PUT, Some(&payload))?;
+        Ok(())
+    }
+
+    pub fn get_steps(&self, project_name: &str, procedure_name: &str) -> Result<Vec<Step>, Error> {
+        let uri = format!("projects/{}/procedures/{}/steps", segment(project_name), segment(procedure_name));
+        let res = &self.request_json(&uri, Method::GET, None)?;
+        let steps: StepsResponse = serde_json::from_str(&res)?;
+        Ok(steps.step)
+    }
+
+    fn step_payload(step: &Step) -> HashMap<&str, &str> {
+        let mut payload = HashMap::new();
+        payload.insert("stepName", step.step_name.as_str());
+        let attributes = [
+            ("command", &step.command),
+            ("shell", &step.shell),
+            ("condition", &step.condition),
+            ("timeLimit", &step.time_limit),
+            ("resourceName", &step.resource_name),
+            ("description", &step.description),
+            ("postProcessor", &step.post_processor),
+        ];
+        for &(name, value) in attributes.iter() {
+            if let Some(ref value) = *value {
+                payload.insert(name, value.as_str());
+            }
+        }
+        payload
+    }
+
+    pub fn create_step(&self, project_name: &str, procedure_name: &str, step: &Step) -> Result<(), Error> {
+        let uri = format!("projects/{}/procedures/{}/steps", segment(project_name), segment(procedure_name));
+        let payload = Self::step_payload(step);
+        let _res = &self.request_json(&uri, Method::POST, Some(&payload))?;
+        Ok(())
+    }
+
+    pub fn modify_step(&self, project_name: &str, procedure_name: &str, step: &Step) -> Result<(), Error> {
+        let uri = format!("projects/{}/procedures/{}/steps/{}", segment(project_name), segment(procedure_name), segment(&step.step_name));
+        let payload = Self::step_payload(step);
+        let _res = &self.request_json(&uri, Method::PUT, Some(&payload))?;
+        Ok(())
+    }
+
+    pub fn delete_step(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<(), Error> {
+        let uri = format!("projects/{}/procedures/{}/steps/{}", segment(project_name), segment(procedure_name), segment(step_name));
+        let _res = &self.request_json(&uri, Method::DELETE, None)?;
+        Ok(())
+    }
+
+//    Without a step to move before, the step becomes the last one
+    pub fn move_step(&self, project_name: &str, procedure_name: &str, step_name: &str, before_step: Option<&str>) -> Result<(), Error> {
+        let mut uri = format!("projects/{}/procedures/{}/steps/{}?request=moveStep", segment(project_name), segment(procedure_name), segment(step_name));
+        if let Some(before) = before_step {
+            uri.push_str(&format!("&beforeStep={}", segment(before)));
+        }
+        let _res = &self.request_json(&uri, Method::POST, None)?;
+        Ok(())
+    }
+
+    pub fn get_formal_parameters(&self, project_name: &str, procedure_name: &str) -> Result<Vec<FormalParameter>, Error> {
+        let uri = format!("projects/{}/procedures/{}/formalParameters", segment(project_name), segment(procedure_name));
+        let res = &self.request_json(&uri, Method::GET, None)?;
+        let parameters: FormalParametersResponse = serde_json::from_str(&res)?;
+        Ok(parameters.formal_parameters)
+    }
+
+    pub fn get_formal_parameter(&self, project_name: &str, procedure_name: &str, name: &str) -> Result<FormalParameter, Error> {
+        let uri = format!("projects/{}/procedures/{}/formalParameters/{}", segment(project_name), segment(procedure_name), segment(name));
+        let res = &self.request_json(&uri, Method::GET, None)?;
+        let parameter: FormalParameterResponse = serde_json::from_str(&res)?;
+        Ok(parameter.formal_parameter)
+    }
+
+    pub fn set_formal_parameter_attribute(&self, project_name: &str, procedure_name: &str, name: &str, attribute: &str, value: &str) -> Result<(), Error> {
+        let uri = format!("projects/{}/procedures/{}/formalParameters/{}", segment(project_name), segment(procedure_name), segment(name));
         let mut payload = HashMap::new();
-        payload.insert("command", command);
-        let _res = &self.request_json(&uri, Method::Put, Some(&payload))?;
+        payload.insert(attribute, value);
+        let _res = &self.request_json(&uri, Method::PUT, Some(&payload))?;
+        Ok(())
+    }
+
+    fn formal_parameter_payload(parameter: &FormalParameter) -> HashMap<&str, &str> {
+        let mut payload = HashMap::new();
+        payload.insert("formalParameterName", parameter.name.as_str());
+        if let Some(ref value) = parameter.default_value {
+            payload.insert("defaultValue", value.as_str());
+        }
+        if let Some(ref required) = parameter.required {
+            payload.insert("required", required.as_str());
+        }
+        if let Some(ref parameter_type) = parameter.parameter_type {
+            payload.insert("type", parameter_type.as_str());
+        }
+        if let Some(ref description) = parameter.description {
+            payload.insert("description", description.as_str());
+        }
+        payload
+    }
+
+    pub fn create_formal_parameter(&self, project_name: &str, procedure_name: &str, parameter: &FormalParameter) -> Result<(), Error> {
+        let uri = format!("projects/{}/procedures/{}/formalParameters", segment(project_name), segment(procedure_name));
+        let payload = Self::formal_parameter_payload(parameter);
+        let _res = &self.request_json(&uri, Method::POST, Some(&payload))?;
+        Ok(())
+    }
+
+    pub fn modify_formal_parameter(&self, project_name: &str, procedure_name: &str, parameter: &FormalParameter) -> Result<(), Error> {
+        let uri = format!("projects/{}/procedures/{}/formalParameters/{}", segment(project_name), segment(procedure_name), segment(&parameter.name));
+        let payload = Self::formal_parameter_payload(parameter);
+        let _res = &self.request_json(&uri, Method::PUT, Some(&payload))?;
+        Ok(())
+    }
+
+    pub fn delete_formal_parameter(&self, project_name: &str, procedure_name: &str, name: &str) -> Result<(), Error> {
+        let uri = format!("projects/{}/procedures/{}/formalParameters/{}", segment(project_name), segment(procedure_name), segment(name));
+        let _res = &self.request_json(&uri, Method::DELETE, None)?;
+        Ok(())
+    }
+
+    pub fn eval_dsl(&self, dsl: &str) -> Result<(), Error> {
+        let mut payload = HashMap::new();
+        payload.insert("dsl", dsl);
+        let _res = &self.request_json("server/dsl", Method::POST, Some(&payload))?;
         Ok(())
     }
 
     pub fn get_plugin(&self, plugin_name: &str) -> Result<Plugin, Error> {
         let uri  = format!("plugins/{}", plugin_name);
-        let res = &self.request_json(&uri, Method::Get, None)?;
+        let res = &self.request_json(&uri, Method::GET, None)?;
         let plugin: PluginResponse = serde_json::from_str(&res)?;
         Ok(plugin.plugin)
     }
@@ -152,42 +581,16 @@ impl EFClient {
 
     pub fn get_property(&self, name: &str) -> Result<Property, Error> {
         let uri = format!("properties/{}", utf8_percent_encode(name, DEFAULT_ENCODE_SET).to_string());
-        let res = &self.request_json(&uri, Method::Get, None)?;
+        let res = &self.request_json(&uri, Method::GET, None)?;
         let property: PropertyResponse = serde_json::from_str(&res)?;
         Ok(property.property)
     }
 
-    pub fn status(&self) -> () {
+    pub fn status(&self) -> Result<ServerStatus, Error> {
         let uri = "server/status";
-        let res = &self.request_json(&uri, Method::Get, None);
+        let res = &self.request_json(&uri, Method::GET, None)?;
         self.debug(&format!("{:?}", res));
+        let status: ServerStatusResponse = serde_json::from_str(&res)?;
+        Ok(status.server_status)
     }
 }
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    fn build_client() -> EFClient {
-        let ef_client = EFClient::new("vivarium", Some("admin"),
-                                      Some("changeme"), None).unwrap();
-        ef_client
-    }
-
-    #[test]
-    fn get_plugin_test() {
-        let client = build_client();
-        let plugin = client.get_plugin("EC-OpenShift");
-        println!("{:?}", plugin);
-        assert!(plugin.is_ok());
-    }
-
-    #[test]
-    fn set_procedure_command() {
-        let client = build_client();
-        let plugin = client.get_plugin("EC-OpenShift").unwrap();
-        let project_name = plugin.plugin_name;
-        let result = client.set_procedure_command(&project_name, "Discover", "discover", "test");
-        assert!(result.is_ok());
-    }
-}
\ No newline at end of file
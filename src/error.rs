@@ -0,0 +1,95 @@
+use std::error;
+use std::fmt;
+use std::io;
+use reqwest;
+use serde_json;
+use serde_xml_rs;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Transport(String),
+    Http { status: u16, body: String },
+    Auth(String),
+    Response(String),
+    Metadata(String),
+    Mapping(String),
+    Config(String),
+    Conflict(String),
+    Dsl { file: String, line: Option<usize>, message: String },
+}
+
+impl Error {
+    pub fn is_not_found(&self) -> bool {
+        match *self {
+            Error::Http { status, .. } => status == 404,
+            _ => false
+        }
+    }
+
+    pub fn is_auth(&self) -> bool {
+        match *self {
+            Error::Auth(_) => true,
+            _ => false
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(f, "{}", e),
+            Error::Transport(ref message) => write!(f, "Transport error: {}", message),
+            Error::Http { status, ref body } => write!(f, "Request failed: status code {}, body: {}", status, body),
+            Error::Auth(ref message) => write!(f, "Authentication failed: {}", message),
+            Error::Response(ref message) => write!(f, "Cannot parse server response: {}", message),
+            Error::Metadata(ref message) => write!(f, "{}", message),
+            Error::Mapping(ref message) => write!(f, "{}", message),
+            Error::Config(ref message) => write!(f, "{}", message),
+            Error::Conflict(ref message) => write!(f, "Conflict: {}", message),
+            Error::Dsl { ref file, line: Some(line), ref message } => write!(f, "{}:{}: {}", file, line, message),
+            Error::Dsl { ref file, line: None, ref message } => write!(f, "{}: {}", file, message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(_) => "I/O error",
+            Error::Transport(_) => "transport error",
+            Error::Http { .. } => "HTTP error",
+            Error::Auth(_) => "authentication error",
+            Error::Response(_) => "invalid server response",
+            Error::Metadata(_) => "invalid plugin metadata",
+            Error::Mapping(_) => "cannot map file to the server object",
+            Error::Config(_) => "invalid configuration",
+            Error::Conflict(_) => "server value was changed by someone else",
+            Error::Dsl { .. } => "DSL evaluation failed",
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Error {
+        Error::Transport(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Error {
+        Error::Response(e.to_string())
+    }
+}
+
+impl From<serde_xml_rs::Error> for Error {
+    fn from(e: serde_xml_rs::Error) -> Error {
+        Error::Metadata(e.to_string())
+    }
+}
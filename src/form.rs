@@ -0,0 +1,225 @@
+use serde_xml_rs::from_reader;
+use serde_xml_rs;
+use error::Error;
+use ef_client::FormalParameter;
+use server::FlowServer;
+
+#[derive(Debug, Deserialize)]
+struct Editor {
+    #[serde(rename="formElement", default)]
+    elements: Vec<FormElement>,
+}
+
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct FormElement {
+    #[serde(rename="type")]
+    pub element_type: String,
+    pub property: String,
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub required: Option<String>,
+    #[serde(default)]
+    pub documentation: Option<String>,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(rename="initiallyChecked", default)]
+    pub initially_checked: Option<String>,
+    #[serde(rename="checkedValue", default)]
+    pub checked_value: Option<String>,
+    #[serde(rename="uncheckedValue", default)]
+    pub unchecked_value: Option<String>,
+}
+
+fn is_true(value: &Option<String>) -> bool {
+    match *value {
+        Some(ref value) => value.trim() == "1" || value.trim() == "true",
+        None => false
+    }
+}
+
+impl FormElement {
+    pub fn is_required(&self) -> bool {
+        is_true(&self.required)
+    }
+
+//    A checkbox starts with its checked or unchecked value, "true" and "false" unless the form says otherwise
+    pub fn default_value(&self) -> Option<String> {
+        if self.element_type != "checkbox" || (self.initially_checked.is_none() && self.value.is_some()) {
+            return self.value.clone();
+        }
+        if is_true(&self.initially_checked) {
+            Some(self.checked_value.clone().unwrap_or_else(|| String::from("true")))
+        } else {
+            Some(self.unchecked_value.clone().unwrap_or_else(|| String::from("false")))
+        }
+    }
+
+//    Every field is sent, an empty one clears the value left on the server by an older form
+    pub fn formal_parameter(&self) -> FormalParameter {
+        FormalParameter {
+            name: self.property.clone(),
+            default_value: Some(self.default_value().unwrap_or_default()),
+            required: Some(String::from(if self.is_required() { "1" } else { "0" })),
+            parameter_type: Some(self.element_type.clone()),
+            description: Some(self.documentation.clone().unwrap_or_default()),
+        }
+    }
+}
+
+pub fn parse(contents: &str) -> Result<Vec<FormElement>, Error> {
+    let editor: Result<Editor, serde_xml_rs::Error> = from_reader(contents.as_bytes());
+    match editor {
+        Ok(editor) => Ok(editor.elements),
+        Err(e) => Err(Error::Mapping(format!("Cannot parse form: {}", e)))
+    }
+}
+
+pub fn form_property(plugin_key: &str, procedure: &str) -> String {
+    format!("/plugins/{}/project/procedures/{}/ec_parameterForm", plugin_key, procedure)
+}
+
+fn form_type_property(plugin_key: &str, procedure: &str, parameter: &str) -> String {
+    format!("/plugins/{}/project/procedures/{}/ec_customEditorData/parameters/{}/formType", plugin_key, procedure, parameter)
+}
+
+//    The server returns "true" for a required parameter and may leave out empty fields
+fn differs(current: &FormalParameter, parameter: &FormalParameter) -> bool {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    is_true(&current.required) != is_true(&parameter.required)
+        || text(&current.parameter_type) != text(&parameter.parameter_type)
+        || text(&current.default_value) != text(&parameter.default_value)
+        || text(&current.description) != text(&parameter.description)
+}
+
+//    Makes the formal parameters of the procedure and their editor data match the form fields
+pub fn reconcile<S: FlowServer>(server: &S, plugin_key: &str, project: &str, procedure: &str, elements: &[FormElement], allow_delete: bool) -> Result<(), Error> {
+    let existing = server.get_formal_parameters(project, procedure)?;
+    for element in elements {
+        let parameter = element.formal_parameter();
+        match existing.iter().find(|p| p.name == parameter.name) {
+            None => {
+                println!("Creating parameter {} of procedure {}", parameter.name, procedure);
+                server.create_formal_parameter(project, procedure, &parameter)?;
+                server.set_property(&form_type_property(plugin_key, procedure, &parameter.name), "standard")?;
+            },
+            Some(current) if differs(current, &parameter) => {
+                println!("Updating parameter {} of procedure {}", parameter.name, procedure);
+                server.modify_formal_parameter(project, procedure, &parameter)?;
+                server.set_property(&form_type_property(plugin_key, procedure, &parameter.name), "standard")?;
+            },
+            Some(_) => {}
+        }
+    }
+    for parameter in existing.iter() {
+        if elements.iter().any(|e| e.property == parameter.name) {
+            continue;
+        }
+        if allow_delete {
+            println!("Deleting parameter {} of procedure {}", parameter.name, procedure);
+            server.delete_formal_parameter(project, procedure, &parameter.name)?;
+        } else {
+            println!("Keeping parameter {} of procedure {} on the server, use --allow-delete to delete it", parameter.name, procedure);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use server::{Call, RecordingServer};
+
+    static FORM: &str = r#"<editor>
+    <formElement>
+        <type>entry</type>
+        <label>Configuration:</label>
+        <property>config</property>
+        <required>1</required>
+        <documentation>Name of the configuration.</documentation>
+    </formElement>
+    <formElement>
+        <type>checkbox</type>
+        <property>dryRun</property>
+        <checkedValue>true</checkedValue>
+        <value>false</value>
+    </formElement>
+</editor>"#;
+
+    #[test]
+    fn parse_form() {
+        let elements = parse(FORM).unwrap();
+        assert_eq!(elements.len(), 2);
+        assert_eq!(elements[0].property, "config");
+        assert!(elements[0].is_required());
+        assert!(!elements[1].is_required());
+        let parameter = elements[1].formal_parameter();
+        assert_eq!(parameter.default_value, Some(String::from("false")));
+        assert_eq!(parameter.parameter_type, Some(String::from("checkbox")));
+        assert!(parse("<editor><formElement></editor>").is_err());
+    }
+
+    #[test]
+    fn reconcile_parameters() {
+        let server = RecordingServer::new();
+        let obsolete = FormalParameter { name: String::from("obsolete"), ..FormalParameter::default() };
+        server.create_formal_parameter("EC-Test-1.0.0", "Deploy", &obsolete).unwrap();
+        let mut changed = parse(FORM).unwrap()[0].formal_parameter();
+        changed.required = Some(String::from("0"));
+        server.create_formal_parameter("EC-Test-1.0.0", "Deploy", &changed).unwrap();
+
+        let elements = parse(FORM).unwrap();
+        reconcile(&server, "EC-Test", "EC-Test-1.0.0", "Deploy", &elements, false).unwrap();
+        assert_eq!(server.formal_parameters("EC-Test-1.0.0", "Deploy").len(), 3);
+        assert_eq!(server.property("/plugins/EC-Test/project/procedures/Deploy/ec_customEditorData/parameters/config/formType"),
+                   Some(String::from("standard")));
+        reconcile(&server, "EC-Test", "EC-Test-1.0.0", "Deploy", &elements, true).unwrap();
+
+        let parameters = server.formal_parameters("EC-Test-1.0.0", "Deploy");
+        let names: Vec<&str> = parameters.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, vec!["config", "dryRun"]);
+        assert_eq!(parameters[0], elements[0].formal_parameter());
+        assert!(server.calls().contains(&Call::DeleteFormalParameter {
+            project: String::from("EC-Test-1.0.0"),
+            procedure: String::from("Deploy"),
+            name: String::from("obsolete"),
+        }));
+        assert_eq!(server.property("/plugins/EC-Test/project/procedures/Deploy/ec_customEditorData/parameters/dryRun/formType"),
+                   Some(String::from("standard")));
+
+        let calls = server.calls().len();
+        reconcile(&server, "EC-Test", "EC-Test-1.0.0", "Deploy", &elements, true).unwrap();
+        assert_eq!(server.calls().len(), calls);
+    }
+
+    #[test]
+    fn checkbox_default() {
+        let form = "<editor><formElement><type>checkbox</type><property>a</property><initiallyChecked>1</initiallyChecked>\
+                    <checkedValue>yes</checkedValue><uncheckedValue>no</uncheckedValue></formElement>\
+                    <formElement><type>checkbox</type><property>b</property></formElement></editor>";
+        let elements = parse(form).unwrap();
+        assert_eq!(elements[0].default_value(), Some(String::from("yes")));
+        assert_eq!(elements[1].default_value(), Some(String::from("false")));
+    }
+
+    #[test]
+    fn normalized_parameters_are_not_modified() {
+        let server = RecordingServer::new();
+        let elements = parse(FORM).unwrap();
+        let mut normalized = elements[0].formal_parameter();
+        normalized.required = Some(String::from("true"));
+        normalized.default_value = None;
+        server.create_formal_parameter("EC-Test-1.0.0", "Deploy", &normalized).unwrap();
+        let calls = server.calls().len();
+        reconcile(&server, "EC-Test", "EC-Test-1.0.0", "Deploy", &elements[..1], false).unwrap();
+        assert_eq!(server.calls().len(), calls);
+
+//        A description removed from the form is cleared
+        let mut documented = elements[1].formal_parameter();
+        documented.description = Some(String::from("Old documentation"));
+        server.create_formal_parameter("EC-Test-1.0.0", "Deploy", &documented).unwrap();
+        reconcile(&server, "EC-Test", "EC-Test-1.0.0", "Deploy", &elements, false).unwrap();
+        let parameters = server.formal_parameters("EC-Test-1.0.0", "Deploy");
+        assert_eq!(parameters[1].description, Some(String::new()));
+    }
+}
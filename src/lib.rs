@@ -6,6 +6,19 @@ extern crate serde_json;
 extern crate reqwest;
 extern crate url;
 extern crate shellexpand;
+extern crate notify;
+extern crate toml;
 
+pub mod error;
 pub mod updater;
-pub mod ef_client;
\ No newline at end of file
+pub mod ef_client;
+pub mod sync;
+pub mod server;
+pub mod watcher;
+pub mod session;
+pub mod config;
+pub mod diff;
+pub mod state;
+pub mod form;
+pub mod dsl;
+pub mod xpath;
\ No newline at end of file
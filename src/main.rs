@@ -1,26 +1,24 @@
 extern crate rusty_sentry;
-extern crate notify;
 extern crate getopts;
-#[macro_use]
-extern crate serde_derive;
-extern crate serde_xml_rs;
-extern crate url;
 extern crate shellexpand;
 
 use std::env;
+use std::io;
+use std::io::Write;
 use std::path::PathBuf;
 use std::process::exit;
-use std::time::Duration;
-use std::sync::mpsc::channel;
-use std::fs::File;
+use std::fs;
 use std::error::Error;
-use std::io::prelude::*;
+use std::time::Duration;
 use getopts::Options;
-use url::Url;
-use notify::{RecommendedWatcher, Watcher, RecursiveMode, DebouncedEvent};
-use rusty_sentry::updater::{PartialUpdate, guess_plugin_type, PluginType, PluginWizard, PluginGradle, UpdateOptions};
-use rusty_sentry::ef_client::EFClient;
-use serde_xml_rs::deserialize;
+use rusty_sentry::updater::{PartialUpdate, find_plugins, DetectedPlugin, UpdateOptions};
+use rusty_sentry::ef_client::{EFClient, TlsOptions};
+use rusty_sentry::server::FlowServer;
+use rusty_sentry::session;
+use rusty_sentry::config::{Config, Profile, AuthMethod};
+use rusty_sentry::sync;
+use rusty_sentry::diff;
+use rusty_sentry::watcher::watch;
 use shellexpand::tilde;
 
 const SERVER: &str = "s";
@@ -28,24 +26,39 @@ const USERNAME: &str = "u";
 const PASSWORD: &str = "p";
 const PATH: &str = "path";
 const SID: &str = "sid";
-const KEEP_EXTENSIONS: &str = "ke";
+const KEEP_EXTENSIONS: &str = "keep-extensions";
+const DRY_RUN: &str = "dry-run";
+const RETRIES: &str = "retries";
+const RETRY_DELAY: &str = "retry-delay";
+const INSECURE: &str = "insecure";
+const CA_BUNDLE: &str = "ca-bundle";
+const PROFILE: &str = "profile";
+const NO_SYNC: &str = "no-sync";
+const CHECK_CONFLICTS: &str = "check-conflicts";
+const ALLOW_DELETE: &str = "allow-delete";
+const EVAL_DSL: &str = "eval-dsl";
 
-#[derive(Deserialize, Debug)]
-struct Session {
-    url: String,
-    #[serde(rename="sessionId", default)]
-    session_id: String,
-}
+const COMMANDS: [(&str, &str); 6] = [
+    ("watch", "Watch the plugin folder and push every change to the server"),
+    ("push", "Push every mapped file of the plugin to the server"),
+    ("pull", "Write the server values of mapped files back to the plugin folder"),
+    ("diff", "Compare mapped files with their values on the server"),
+    ("status", "Show the server status and the installed plugin version"),
+    ("login", "Log in to the server and store the session"),
+];
 
-#[derive(Deserialize, Debug)]
-struct Sessions {
-    #[serde(rename = "session", default)]
-    sessions: Vec<Session>
+fn print_usage(program: &str, command: &str, opts: Options) {
+    let brief = format!("Usage: {} {} [options]", program, command);
+    print!("{}", opts.usage(&brief));
 }
 
-fn print_usage(program: &str, opts: Options) {
-    let brief = format!("Usage: {} COMMAND [options]", program);
-    print!("{}", opts.usage(&brief));
+fn print_commands(program: &str) {
+    println!("Usage: {} COMMAND [options]\n", program);
+    println!("Commands:");
+    for &(name, description) in COMMANDS.iter() {
+        println!("    {:<10}{}", name, description);
+    }
+    println!("\nRun '{} COMMAND --help' to see the options of a command.", program);
 }
 
 fn print_version() {
@@ -67,76 +80,98 @@ fn print_version() {
 
 
 fn read_sid(server: &str) -> Option<String> {
-    let sid_path = match env::home_dir() {
-        Some(path) => {
-            let mut sid_path = path.clone();
-            sid_path.push(".ecsession");
-            if sid_path.exists() {
-                Some(sid_path)
-            } else {
-                sid_path = path.clone();
-                sid_path.push(".cfsession");
-                if sid_path.exists() {
-                    Some(sid_path)
-                }
-                else {
-                    None
-                }
-            }
-        },
-        None => None
-    };
-
-    if sid_path == None {
-        return None;
-    }
+    session::session_file().and_then(|path| session::read_sid(&path, server))
+}
 
-    let mut file: File;
-    match File::open(sid_path.unwrap()) {
-        Ok(f) => file = f,
-        Err(_) => return None,
-    };
-    let mut contents = String::new();
-    let result = file.read_to_string(&mut contents);
-    if result.is_err() {
-        return None;
-    }
-
-    let sessions: Result<Sessions, serde_xml_rs::Error> = deserialize(contents.as_bytes());
-    match sessions {
-        Ok(s) => {
-            for session in s.sessions {
-                let url = Url::parse(&session.url).unwrap();
-                if url.host_str() == Some(server) {
-                    return Some(session.session_id)
-                }
-            }
-            None
-        },
-        Err(_) => { None }
+fn store_sid(ef_client: &EFClient, sid: &str) {
+    if let Some(path) = session::default_session_file() {
+        match session::write_sid(&path, &ef_client.base_url(), sid) {
+            Ok(_) => println!("Stored session in {}", path.display()),
+            Err(e) => eprintln!("Cannot store session in {}: {}", path.display(), e),
+        }
     }
 }
 
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    let program = args[0].clone();
-
-    print_version();
+//    A flag a profile can turn on, with a --no- variant to turn it off again from the command line
+fn switch(opts: &mut Options, short: &str, name: &str, description: &str) {
+    opts.optflag(short, name, description);
+    opts.optflag("", &format!("no-{}", name), &format!("do not use --{} even if the profile sets it", name));
+}
 
+fn command_options(command: &str) -> Options {
     let mut opts = Options::new();
     opts.optflag("h", "help", "Print this help menu");
-    opts.optflag("", "version", "Show the version");
     opts.optflag("v", "verbose", "print debug output");
 
-    opts.optopt(USERNAME, "username", "Provide username to connect to server", "");
-    opts.reqopt(SERVER, "server", "provide server name to connect", "");
-    opts.optopt("", PATH, "Provide path to the plugin folder", "PATH");
+    opts.optmulti("", PROFILE, "use a named profile from the configuration file, can be repeated; options given on the command line override it, --no-FLAG turns off a flag it sets", "NAME");
+    opts.optmulti(SERVER, "server", "provide server name or url to connect, e.g. flow or http://localhost:8000/prefix, can be repeated", "SERVER");
+    opts.optopt(USERNAME, "username", "Provide username to connect to server", "USERNAME");
     opts.optopt(PASSWORD, "password", "provide password for the server to connect", "PASSWORD");
     opts.optopt("", SID, "provide session id to connect", "SID");
-    opts.optflag("k", "keep-extensions", "keeps file extensions");
+    switch(&mut opts, "", INSECURE, "do not verify the server certificate");
+    opts.optopt("", CA_BUNDLE, "provide a PEM file with additional trusted CA certificates", "FILE");
+    opts.optopt("", RETRIES, "how many times to retry failed GET and PUT requests (default 3)", "COUNT");
+    opts.optopt("", RETRY_DELAY, "delay before the first retry in milliseconds, doubled on every attempt (default 500)", "MS");
 
-    let matches = match opts.parse(&args[1..]) {
+    match command {
+        "watch" | "push" | "pull" | "diff" => {
+            opts.optmulti("", PATH, "Provide path to the plugin folder or to a folder with plugins, can be repeated", "PATH");
+            switch(&mut opts, "k", KEEP_EXTENSIONS, "keeps file extensions");
+        },
+        _ => {}
+    }
+    match command {
+        "watch" | "push" | "pull" => {
+            switch(&mut opts, "", DRY_RUN, "print the changes instead of applying them");
+            switch(&mut opts, "", CHECK_CONFLICTS, "refuse to overwrite server values changed since the last push or pull");
+        },
+        _ => {}
+    }
+    match command {
+        "watch" => {
+            opts.optflag("", NO_SYNC, "do not compare the plugin with the server on start");
+            switch(&mut opts, "", ALLOW_DELETE, "delete server properties of deleted or renamed files, and steps or parameters removed from a procedure");
+            switch(&mut opts, "", EVAL_DSL, "push .dsl files and promote/demote scripts by evaluating them on the server");
+        },
+        "push" => {
+            switch(&mut opts, "", ALLOW_DELETE, "delete steps and parameters removed from a procedure");
+            switch(&mut opts, "", EVAL_DSL, "push .dsl files and promote/demote scripts by evaluating them on the server");
+        },
+        "status" => {
+            opts.optmulti("", PATH, "Provide path to the plugin folder to compare with the installed plugin, can be repeated", "PATH");
+        },
+        _ => {}
+    }
+    opts
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let program = args[0].clone();
+
+    let (command, command_args) = match args.get(1).map(|a| a.as_str()) {
+        None | Some("-h") | Some("--help") | Some("help") => {
+            print_commands(&program);
+            return;
+        },
+        Some("--version") => {
+            print_version();
+            return;
+        },
+//        Options without a command keep the old behaviour of watching the plugin
+        Some(arg) if arg.starts_with("-") => ("watch", &args[1..]),
+        Some(arg) => (arg, &args[2..]),
+    };
+
+    if !COMMANDS.iter().any(|&(name, _)| name == command) {
+        eprintln!("Unknown command: {}", command);
+        print_commands(&program);
+        exit(1);
+    }
+
+    let opts = command_options(command);
+    let matches = match opts.parse(command_args) {
         Ok(m) => { m },
         Err(f) => {
             eprintln!("{}", f.to_string());
@@ -144,80 +179,452 @@ fn main() {
         }
     };
     if matches.opt_present("h") {
-        print_usage(&program, opts);
+        print_usage(&program, command, opts);
+        return;
     }
+    if !matches.free.is_empty() {
+        eprintln!("Unexpected arguments: {}", matches.free.join(" "));
+        print_usage(&program, command, opts);
+        exit(1);
+    }
+
+    let result = match command {
+        "watch" | "push" | "pull" | "diff" => with_plugin(command, &matches),
+        "status" => status(&matches),
+        "login" => login(&matches),
+        _ => Err(From::from(format!("Unknown command: {}", command))),
+    };
 
+    if let Err(e) = result {
+        eprintln!("{} failed: {}", command, e);
+        exit(1);
+    }
+}
 
-    let _command = if !matches.free.is_empty() {
-        matches.free[0].clone()
+fn plugin_paths(matches: &getopts::Matches) -> Vec<PathBuf> {
+    let paths = if matches.opt_defined(PATH) { matches.opt_strs(PATH) } else { Vec::new() };
+    let paths = if paths.is_empty() {
+        vec![env::current_dir().unwrap()]
     } else {
-        String::from("watch")
+        paths.iter().map(|p| PathBuf::from(tilde(p).into_owned())).collect()
     };
+//    Updaters match absolute paths, so relative paths would never be mapped
+    paths.into_iter().map(|path| fs::canonicalize(&path).unwrap_or(path)).collect()
+}
 
-    let path: PathBuf = match matches.opt_str("path") {
-        None => env::current_dir().unwrap(),
-        Some(p) => {
-            PathBuf::from(tilde(&p).into_owned())
-        }
-    };
+//    The configuration is looked up in the first given folder
+fn plugin_path(matches: &getopts::Matches) -> PathBuf {
+    plugin_paths(matches).remove(0)
+}
 
-    let ef_client = match build_client(&matches) {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("Error while creating client: {}", e);
-            exit(-1);
-        }
-    };
+fn plugin_folders(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Box<Error>> {
+    let mut folders = Vec::new();
+    for path in paths {
+        let found = find_plugins(path)
+            .map_err(|e| format!("Cannot deduce plugin type: {}", e))?;
+        folders.extend(found);
+    }
+    Ok(folders)
+}
+
+fn load_profiles(matches: &getopts::Matches) -> Result<Vec<Profile>, Box<Error>> {
+//    The plugin configuration overrides the user one, command line options override both
+    let config = Config::load(&plugin_path(matches))?;
+    let names = matches.opt_strs(PROFILE);
+    if names.is_empty() {
+        return Ok(vec![config.profile(None)?]);
+    }
+    let mut profiles = Vec::new();
+    for name in names.iter() {
+        profiles.push(config.profile(Some(name))?);
+    }
+    Ok(profiles)
+}
 
-    let ke: bool = if matches.opt_present("k") {
+//    The command line wins over the profile, a flag given both ways is on
+fn flag(matches: &getopts::Matches, name: &str, profile_value: Option<bool>) -> bool {
+    let negated = format!("no-{}", name);
+    if matches.opt_defined(name) && matches.opt_present(name) {
         true
-    } else {
+    } else if matches.opt_defined(&negated) && matches.opt_present(&negated) {
         false
-    };
+    } else {
+        profile_value.unwrap_or(false)
+    }
+}
+
+fn update_options(matches: &getopts::Matches, profile: &Profile) -> UpdateOptions {
+    let ke = flag(matches, KEEP_EXTENSIONS, profile.keep_extensions);
+    let dry_run = flag(matches, DRY_RUN, profile.dry_run);
+    let check_conflicts = flag(matches, CHECK_CONFLICTS, profile.check_conflicts);
+    let allow_delete = flag(matches, ALLOW_DELETE, profile.allow_delete);
+    let eval_dsl = flag(matches, EVAL_DSL, profile.eval_dsl);
+    UpdateOptions {
+        keep_extensions: ke,
+        dry_run,
+        check_conflicts,
+        allow_delete,
+        eval_dsl,
+    }
+}
+
+struct Target {
+    client: EFClient,
+    options: UpdateOptions,
+}
 
-    let options = UpdateOptions {
-        keep_extensions: ke
+//    Each --server is paired with the profile at the same position, or the last one,
+//    without --server every profile connects to its own server
+fn build_targets(matches: &getopts::Matches) -> Result<Vec<Target>, Box<Error>> {
+    let servers = matches.opt_strs(SERVER);
+    let profiles = load_profiles(matches)?;
+    let pairs: Vec<(&Profile, Option<&str>)> = if servers.is_empty() {
+        profiles.iter().map(|profile| (profile, None)).collect()
+    } else {
+        servers.iter().enumerate()
+            .map(|(i, server)| (profiles.get(i).unwrap_or(&profiles[profiles.len() - 1]), Some(server.as_str())))
+            .collect()
     };
+    let mut targets: Vec<Target> = Vec::new();
+    let mut selected: Vec<String> = Vec::new();
+    let mut first_error = None;
+    for (profile, server) in pairs {
+//        Duplicates are dropped before logging in, so every server is logged in to once
+        let client = match build_client(matches, profile, server) {
+            Ok(ref client) if selected.contains(&client.base_url()) => {
+                println!("Skipping {}, it is already selected", client.base_url());
+                continue;
+            },
+            Ok(client) => {
+                selected.push(client.base_url());
+                log_in(client)
+            },
+            Err(e) => Err(e),
+        };
+        match client {
+            Ok(client) => targets.push(Target { client, options: update_options(matches, profile) }),
+            Err(e) => {
+                eprintln!("Error while creating client for {}: {}", server.map(String::from).or(profile.server.clone()).unwrap_or_default(), e);
+                first_error = first_error.or(Some(e));
+            }
+        }
+    }
+    match first_error {
+        Some(e) if targets.is_empty() => Err(From::from(format!("Error while creating client: {}", e))),
+        _ => Ok(targets)
+    }
+}
+
+fn log_in(client: EFClient) -> Result<EFClient, Box<Error>> {
+    if client.has_credentials() {
+        let sid = client.login()?;
+        store_sid(&client, &sid);
+    }
+    Ok(client)
+}
+
+fn with_plugin(command: &str, matches: &getopts::Matches) -> Result<(), Box<Error>> {
+    let paths = plugin_paths(matches);
+    let folders = plugin_folders(&paths)?;
+    let targets = build_targets(matches)?;
+    with_targets(command, matches, &paths, &folders, &targets)
+}
+
+fn with_targets(command: &str, matches: &getopts::Matches, paths: &[PathBuf], folders: &[PathBuf], targets: &[Target]) -> Result<(), Box<Error>> {
+    let mut updaters = Vec::new();
+    for folder in folders.iter() {
+        for target in targets.iter() {
+            let updater = DetectedPlugin::build(folder, target.client.clone(), target.options.clone())
+                .map_err(|e| format!("Cannot build updater for {}: {}", folder.display(), e))?;
+            updaters.push(updater);
+        }
+    }
+    run_command(command, matches, paths, &updaters)
+}
 
-    let plugin_type = guess_plugin_type(&path);
-    let result = match plugin_type {
-        Ok(PluginType::PluginWizard) => {
-            let updater = PluginWizard::build(&path, ef_client, options);
-            match updater {
-                Ok(upd) => watch(&path, &upd),
-                Err(e) => {
-                    eprintln!("Cannot build updater: {}", e);
-                    exit(1)
+fn run_command<T>(command: &str, matches: &getopts::Matches, paths: &[PathBuf], plugins: &[T]) -> Result<(), Box<Error>> where T: PartialUpdate {
+    match command {
+        "watch" => {
+            print_version();
+            if !flag(matches, NO_SYNC, None) {
+                initial_sync(plugins)?;
+            }
+            watch(paths, plugins)?;
+            Ok(())
+        },
+        "push" => {
+            let mut failed = 0;
+            for plugin in plugins {
+                if plugins.len() > 1 {
+                    println!("\nPushing {} to {}", plugin.meta().key(), plugin.server().name());
+                }
+                if let Err(e) = push(plugin) {
+                    eprintln!("Push of {} to {} failed: {}", plugin.meta().key(), plugin.server().name(), e);
+                    failed += 1;
                 }
             }
+            if failed > 0 {
+                return Err(From::from(format!("push failed for {} of {} plugin targets", failed, plugins.len())));
+            }
+            Ok(())
         },
-        Ok(PluginType::Gradle) => {
-            let updater = PluginGradle::build(&path, ef_client, options);
-            match updater {
-                Ok(upd) => watch(&path, &upd),
-                Err(e) => {
-                    eprintln!("Cannot build updater: {}", e);
-                    exit(1)
+        "pull" => {
+            let mut failed = 0;
+            for plugin in plugins {
+                if plugins.len() > 1 {
+                    println!("\nPulling {} from {}", plugin.meta().key(), plugin.server().name());
+                }
+                if let Err(e) = pull(plugin) {
+                    eprintln!("Pull of {} from {} failed: {}", plugin.meta().key(), plugin.server().name(), e);
+                    failed += 1;
+                }
+            }
+            if failed > 0 {
+                return Err(From::from(format!("pull failed for {} of {} plugin targets", failed, plugins.len())));
+            }
+            Ok(())
+        },
+        "diff" => {
+            let mut failed = 0;
+            for plugin in plugins {
+                if plugins.len() > 1 {
+                    println!("\nComparing {} with {}", plugin.meta().key(), plugin.server().name());
+                }
+                if let Err(e) = diff(plugin) {
+                    eprintln!("Diff of {} with {} failed: {}", plugin.meta().key(), plugin.server().name(), e);
+                    failed += 1;
                 }
             }
+            if failed > 0 {
+                return Err(From::from(format!("diff failed for {} of {} plugin targets", failed, plugins.len())));
+            }
+            Ok(())
+        },
+        "status" => {
+            for plugin in plugins {
+                plugin_status(plugin);
+            }
+            Ok(())
+        },
+        _ => Err(From::from(format!("{} cannot be run for a plugin", command)))
+    }
+}
+
+fn push<T>(plugin: &T) -> Result<(), Box<Error>> where T: PartialUpdate {
+    let summary = sync::push(plugin)?;
+    let path = plugin.meta().folder();
+    let relative = |file: &PathBuf| file.strip_prefix(path).unwrap_or(file).display().to_string();
+
+    println!();
+    for file in summary.updated.iter() {
+        println!("updated  {}", relative(file));
+    }
+    for &(ref file, ref mutation) in summary.recorded.iter() {
+        println!("dry-run  {}: {}", relative(file), mutation);
+    }
+    for file in summary.unchanged.iter() {
+        println!("unchanged {}", relative(file));
+    }
+    for file in summary.skipped.iter() {
+        println!("skipped  {}", relative(file));
+    }
+    for &(ref file, ref e) in summary.failed.iter() {
+        println!("failed   {}: {}", relative(file), e);
+    }
+    if summary.recorded.is_empty() {
+        println!("\n{} updated, {} unchanged, {} skipped, {} failed",
+                 summary.updated.len(), summary.unchanged.len(), summary.skipped.len(), summary.failed.len());
+    } else {
+        println!("\n{} would be updated, {} skipped, {} failed", summary.recorded.len(), summary.skipped.len(), summary.failed.len());
+    }
+
+    if !summary.failed.is_empty() {
+        return Err(From::from(format!("{} files failed to update", summary.failed.len())));
+    }
+    Ok(())
+}
+
+fn pull<T>(plugin: &T) -> Result<(), Box<Error>> where T: PartialUpdate {
+    let summary = sync::pull(plugin)?;
+    let path = plugin.meta().folder();
+    let relative = |file: &PathBuf| file.strip_prefix(path).unwrap_or(file).display().to_string();
+
+    println!();
+    for file in summary.pulled.iter() {
+        println!("pulled   {}", relative(file));
+    }
+    for file in summary.recorded.iter() {
+        println!("dry-run  {}", relative(file));
+    }
+    for file in summary.missing.iter() {
+        println!("missing  {}", relative(file));
+    }
+    for &(ref file, ref e) in summary.failed.iter() {
+        println!("failed   {}: {}", relative(file), e);
+    }
+    if summary.recorded.is_empty() {
+        println!("\n{} pulled, {} in sync, {} missing on the server, {} failed",
+                 summary.pulled.len(), summary.in_sync.len(), summary.missing.len(), summary.failed.len());
+    } else {
+        println!("\n{} would be pulled, {} in sync, {} missing on the server, {} failed",
+                 summary.recorded.len(), summary.in_sync.len(), summary.missing.len(), summary.failed.len());
+    }
+
+    if !summary.failed.is_empty() {
+        return Err(From::from(format!("{} files failed to pull", summary.failed.len())));
+    }
+    Ok(())
+}
+
+fn diff<T>(plugin: &T) -> Result<(), Box<Error>> where T: PartialUpdate {
+    let summary = sync::compare(plugin)?;
+    let path = plugin.meta().folder();
+    let relative = |file: &PathBuf| file.strip_prefix(path).unwrap_or(file).display().to_string();
+
+    for &(ref file, ref mutation, ref remote) in summary.changed.iter() {
+        let server_name = format!("server: {}", mutation.target);
+        let local_name = format!("local: {}", relative(file));
+        print!("{}", diff::unified_diff(remote, &mutation.value, &server_name, &local_name, 3));
+    }
+    println!();
+    for &(ref file, _, _) in summary.changed.iter() {
+        println!("changed  {}", relative(file));
+    }
+    for &(ref file, ref mutation) in summary.missing.iter() {
+        println!("missing  {}: {} does not exist on the server", relative(file), mutation.target);
+    }
+    for file in summary.skipped.iter() {
+        println!("skipped  {}", relative(file));
+    }
+    for &(ref file, ref e) in summary.failed.iter() {
+        println!("failed   {}: {}", relative(file), e);
+    }
+    println!("\n{} in sync, {} changed, {} missing on the server, {} skipped, {} failed",
+             summary.in_sync.len(), summary.changed.len(), summary.missing.len(), summary.skipped.len(), summary.failed.len());
+
+    if !summary.failed.is_empty() {
+        return Err(From::from(format!("{} files could not be compared", summary.failed.len())));
+    }
+    Ok(())
+}
+
+fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    let _ = io::stdout().flush();
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(_) => {
+            let answer = answer.trim().to_lowercase();
+            answer == "y" || answer == "yes"
         },
-        Err(e) => {
-            eprintln!("Cannot deduce plugin type: {}", e);
-            exit(1);
+        Err(_) => false
+    }
+}
+
+//    Changes made while the watcher was down would be missed until the files are touched again
+fn initial_sync<T>(plugins: &[T]) -> Result<(), Box<Error>> where T: PartialUpdate {
+    let mut differing = Vec::new();
+    for plugin in plugins {
+        println!("Comparing {} with {}", plugin.meta().key(), plugin.server().name());
+        let summary = sync::compare(plugin)?;
+        let path = plugin.meta().folder();
+        let relative = |file: &PathBuf| file.strip_prefix(path).unwrap_or(file).display().to_string();
+        for &(ref file, _, _) in summary.changed.iter() {
+            println!("changed  {}", relative(file));
         }
-    };
+        for &(ref file, _) in summary.missing.iter() {
+            println!("missing  {}", relative(file));
+        }
+        for &(ref file, ref e) in summary.failed.iter() {
+            println!("failed   {}: {}", relative(file), e);
+        }
+        sync::record_in_sync(plugin, &summary);
+        for file in summary.differing() {
+            differing.push((plugin, file.clone()));
+        }
+    }
+    if differing.is_empty() {
+        println!("Server is in sync with the local files");
+        return Ok(());
+    }
+    if !confirm(&format!("Push {} differing files to the server?", differing.len())) {
+        return Ok(());
+    }
+//    The server values were shown and the overwrite confirmed, so they are not conflicts
+    for (plugin, file) in differing {
+        if let Err(e) = plugin.update_with(&file, true) {
+            eprintln!("Cannot push {} to {}: {}", file.display(), plugin.server().name(), e);
+        }
+    }
+    Ok(())
+}
 
-    if result.is_err() {
-        eprintln!("Watch failed: {}", result.unwrap_err());
-        exit(1);
-    };
+fn login(matches: &getopts::Matches) -> Result<(), Box<Error>> {
+    for profile in load_profiles(matches)? {
+        if matches.opt_str(USERNAME).or(profile.username.clone()).is_none()
+            || matches.opt_str(PASSWORD).or(profile.password.clone()).is_none() {
+            return Err(From::from("Username and password must be provided"));
+        }
+    }
+    for target in build_targets(matches)? {
+        match target.client.session_id() {
+            Some(sid) => println!("Logged in to {}, session id: {}", target.client.base_url(), sid),
+            None => return Err(From::from("Login did not return a session")),
+        }
+    }
+    Ok(())
 }
 
-fn build_client(matches: &getopts::Matches) -> Result<EFClient, Box<Error>> {
-    let server = matches.opt_str(SERVER).expect("Server must be provided");
-    let username = matches.opt_str(USERNAME);
-    let password = matches.opt_str(PASSWORD);
-    let mut sid = matches.opt_str(SID);
+fn status(matches: &getopts::Matches) -> Result<(), Box<Error>> {
+    let targets = build_targets(matches)?;
+    for target in targets.iter() {
+        if targets.len() > 1 {
+            println!("{}", target.client.base_url());
+        }
+        match target.client.status() {
+            Ok(server_status) => {
+                println!("Server state: {}", server_status.server_state);
+                if !server_status.last_message.is_empty() {
+                    println!("Last message: {}", server_status.last_message);
+                }
+            },
+            Err(e) => eprintln!("Cannot get the status of {}: {}", target.client.base_url(), e),
+        }
+    }
+
+    let paths = plugin_paths(matches);
+    if let Ok(folders) = plugin_folders(&paths) {
+        with_targets("status", matches, &paths, &folders, &targets)?;
+    }
+    Ok(())
+}
+
+fn plugin_status<T>(plugin: &T) where T: PartialUpdate {
+    let meta = plugin.meta();
+    println!("Local plugin: {} {}", meta.key(), meta.version());
+    match plugin.server().get_plugin(meta.key()) {
+        Ok(installed) => {
+            println!("Installed plugin: {} (version {})", installed.plugin_name, installed.plugin_version);
+            if installed.plugin_version != meta.version() {
+                println!("Installed version differs from the local one");
+            }
+        },
+        Err(e) => println!("Plugin {} is not installed: {}", meta.key(), e),
+    }
+}
+
+fn build_client(matches: &getopts::Matches, profile: &Profile, server_override: Option<&str>) -> Result<EFClient, Box<Error>> {
+    let server = match server_override.map(String::from).or(profile.server.clone()) {
+        Some(s) => s,
+        None => return Err(From::from("Server must be provided")),
+    };
+//    A session profile never logs in with the stored password
+    let (username, password) = match profile.auth {
+        Some(AuthMethod::Session) => (matches.opt_str(USERNAME), matches.opt_str(PASSWORD)),
+        _ => (matches.opt_str(USERNAME).or(profile.username.clone()),
+              matches.opt_str(PASSWORD).or(profile.password.clone())),
+    };
+    let mut sid = matches.opt_str(SID).or(profile.sid.clone());
     let mut debug = 0;
     if matches.opt_present("v") {
         println!("Debug output enabled");
@@ -227,7 +634,7 @@ fn build_client(matches: &getopts::Matches) -> Result<EFClient, Box<Error>> {
         sid = read_sid(&server);
     }
 
-    let mut client = EFClient::new(&server,
+    let client = EFClient::new(&server,
                                username.as_ref().map(|x| &**x),
                                password.as_ref().map(|x| &**x),
                                sid.as_ref().map(|x| &**x));
@@ -235,34 +642,28 @@ fn build_client(matches: &getopts::Matches) -> Result<EFClient, Box<Error>> {
     match client {
         Ok(mut c) => {
             c.set_debug_level(debug);
+            if let Some(port) = profile.port {
+                if server_override.is_none() {
+                    c.set_port(&port.to_string())?;
+                }
+            }
+            let tls = TlsOptions {
+                insecure: flag(matches, INSECURE, profile.insecure),
+                ca_bundle: matches.opt_str(CA_BUNDLE).or(profile.ca_bundle.clone())
+                    .map(|p| PathBuf::from(tilde(&p).into_owned())),
+            };
+            if tls.insecure {
+                eprintln!("Warning: server certificate verification is disabled");
+            }
+            c.set_tls(&tls)?;
+            if matches.opt_present(RETRIES) || matches.opt_present(RETRY_DELAY)
+                || profile.retries.is_some() || profile.retry_delay.is_some() {
+                let retries = matches.opt_get_default(RETRIES, profile.retries.unwrap_or(3))?;
+                let delay = matches.opt_get_default(RETRY_DELAY, profile.retry_delay.unwrap_or(500))?;
+                c.set_retries(retries, Duration::from_millis(delay));
+            }
             Ok(c)
         },
         Err(e) => Err(Box::new(e))
     }
 }
-
-
-fn watch<T>(path: &PathBuf, plugin: &T) -> notify::Result<()> where T: PartialUpdate {
-    let (tx, rx) = channel();
-    let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(1))?;
-    watcher.watch(path, RecursiveMode::Recursive)?;
-
-    println!("Started to watch {}", path.to_str().unwrap());
-    loop {
-        match rx.recv() {
-            Ok(DebouncedEvent::Create(path)) | Ok(DebouncedEvent::Chmod(path)) | Ok(DebouncedEvent::Write(path)) => {
-                println!("Updated or created {}", path.to_str().unwrap());
-                let result = plugin.update(&path);
-                if result.is_err() {
-                    eprintln!("Error while updating: {}", result.err().unwrap());
-                }
-            },
-            Ok(event) => {
-                println!("Other event: {:?}", event);
-            },
-            Err(error) => {
-                println!("Watch error: {:?}", error);
-            }
-        }
-    }
-}
@@ -0,0 +1,424 @@
+use error::Error;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use ef_client::{EFClient, Property, Plugin, FormalParameter, Step};
+
+pub trait FlowServer {
+    fn name(&self) -> String;
+    fn set_property(&self, name: &str, value: &str) -> Result<Property, Error>;
+    fn get_property(&self, name: &str) -> Result<Property, Error>;
+    fn delete_property(&self, name: &str) -> Result<(), Error>;
+    fn set_procedure_command(&self, project_name: &str, procedure_name: &str, step_name: &str, command: &str) -> Result<(), Error>;
+    fn get_procedure_command(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<String, Error>;
+    fn get_step(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<Step, Error>;
+    fn set_step_attribute(&self, project_name: &str, procedure_name: &str, step_name: &str, attribute: &str, value: &str) -> Result<(), Error>;
+    fn get_steps(&self, project_name: &str, procedure_name: &str) -> Result<Vec<Step>, Error>;
+    fn create_step(&self, project_name: &str, procedure_name: &str, step: &Step) -> Result<(), Error>;
+    fn modify_step(&self, project_name: &str, procedure_name: &str, step: &Step) -> Result<(), Error>;
+    fn delete_step(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<(), Error>;
+    fn move_step(&self, project_name: &str, procedure_name: &str, step_name: &str, before_step: Option<&str>) -> Result<(), Error>;
+    fn get_formal_parameters(&self, project_name: &str, procedure_name: &str) -> Result<Vec<FormalParameter>, Error>;
+    fn get_formal_parameter(&self, project_name: &str, procedure_name: &str, name: &str) -> Result<FormalParameter, Error>;
+    fn set_formal_parameter_attribute(&self, project_name: &str, procedure_name: &str, name: &str, attribute: &str, value: &str) -> Result<(), Error>;
+    fn create_formal_parameter(&self, project_name: &str, procedure_name: &str, parameter: &FormalParameter) -> Result<(), Error>;
+    fn modify_formal_parameter(&self, project_name: &str, procedure_name: &str, parameter: &FormalParameter) -> Result<(), Error>;
+    fn delete_formal_parameter(&self, project_name: &str, procedure_name: &str, name: &str) -> Result<(), Error>;
+    fn eval_dsl(&self, dsl: &str) -> Result<(), Error>;
+    fn get_plugin(&self, plugin_name: &str) -> Result<Plugin, Error>;
+}
+
+impl FlowServer for EFClient {
+    fn name(&self) -> String {
+        self.base_url()
+    }
+
+    fn set_property(&self, name: &str, value: &str) -> Result<Property, Error> {
+        EFClient::set_property(self, name, value)
+    }
+
+    fn get_property(&self, name: &str) -> Result<Property, Error> {
+        EFClient::get_property(self, name)
+    }
+
+    fn delete_property(&self, name: &str) -> Result<(), Error> {
+        EFClient::delete_property(self, name)
+    }
+
+    fn set_procedure_command(&self, project_name: &str, procedure_name: &str, step_name: &str, command: &str) -> Result<(), Error> {
+        EFClient::set_procedure_command(self, project_name, procedure_name, step_name, command)
+    }
+
+    fn get_procedure_command(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<String, Error> {
+        EFClient::get_procedure_command(self, project_name, procedure_name, step_name)
+    }
+
+    fn get_step(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<Step, Error> {
+        EFClient::get_step(self, project_name, procedure_name, step_name)
+    }
+
+    fn set_step_attribute(&self, project_name: &str, procedure_name: &str, step_name: &str, attribute: &str, value: &str) -> Result<(), Error> {
+        EFClient::set_step_attribute(self, project_name, procedure_name, step_name, attribute, value)
+    }
+
+    fn get_steps(&self, project_name: &str, procedure_name: &str) -> Result<Vec<Step>, Error> {
+        EFClient::get_steps(self, project_name, procedure_name)
+    }
+
+    fn create_step(&self, project_name: &str, procedure_name: &str, step: &Step) -> Result<(), Error> {
+        EFClient::create_step(self, project_name, procedure_name, step)
+    }
+
+    fn modify_step(&self, project_name: &str, procedure_name: &str, step: &Step) -> Result<(), Error> {
+        EFClient::modify_step(self, project_name, procedure_name, step)
+    }
+
+    fn delete_step(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<(), Error> {
+        EFClient::delete_step(self, project_name, procedure_name, step_name)
+    }
+
+    fn move_step(&self, project_name: &str, procedure_name: &str, step_name: &str, before_step: Option<&str>) -> Result<(), Error> {
+        EFClient::move_step(self, project_name, procedure_name, step_name, before_step)
+    }
+
+    fn get_formal_parameters(&self, project_name: &str, procedure_name: &str) -> Result<Vec<FormalParameter>, Error> {
+        EFClient::get_formal_parameters(self, project_name, procedure_name)
+    }
+
+    fn get_formal_parameter(&self, project_name: &str, procedure_name: &str, name: &str) -> Result<FormalParameter, Error> {
+        EFClient::get_formal_parameter(self, project_name, procedure_name, name)
+    }
+
+    fn set_formal_parameter_attribute(&self, project_name: &str, procedure_name: &str, name: &str, attribute: &str, value: &str) -> Result<(), Error> {
+        EFClient::set_formal_parameter_attribute(self, project_name, procedure_name, name, attribute, value)
+    }
+
+    fn create_formal_parameter(&self, project_name: &str, procedure_name: &str, parameter: &FormalParameter) -> Result<(), Error> {
+        EFClient::create_formal_parameter(self, project_name, procedure_name, parameter)
+    }
+
+    fn modify_formal_parameter(&self, project_name: &str, procedure_name: &str, parameter: &FormalParameter) -> Result<(), Error> {
+        EFClient::modify_formal_parameter(self, project_name, procedure_name, parameter)
+    }
+
+    fn delete_formal_parameter(&self, project_name: &str, procedure_name: &str, name: &str) -> Result<(), Error> {
+        EFClient::delete_formal_parameter(self, project_name, procedure_name, name)
+    }
+
+    fn eval_dsl(&self, dsl: &str) -> Result<(), Error> {
+        EFClient::eval_dsl(self, dsl)
+    }
+
+    fn get_plugin(&self, plugin_name: &str) -> Result<Plugin, Error> {
+        EFClient::get_plugin(self, plugin_name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    SetProperty { name: String, value: String },
+    DeleteProperty { name: String },
+    CreateStep { project: String, procedure: String, step: String },
+    ModifyStep { project: String, procedure: String, step: String },
+    DeleteStep { project: String, procedure: String, step: String },
+    MoveStep { project: String, procedure: String, step: String, before: Option<String> },
+    CreateFormalParameter { project: String, procedure: String, name: String },
+    ModifyFormalParameter { project: String, procedure: String, name: String },
+    DeleteFormalParameter { project: String, procedure: String, name: String },
+    EvalDsl { dsl: String },
+    SetStepAttribute { project: String, procedure: String, step: String, attribute: String, value: String },
+    SetFormalParameterAttribute { project: String, procedure: String, name: String, attribute: String, value: String },
+    SetProcedureCommand { project: String, procedure: String, step: String, command: String },
+}
+
+#[derive(Debug, Default)]
+pub struct RecordingServer {
+    plugins: HashMap<String, Plugin>,
+    properties: RefCell<HashMap<String, String>>,
+    commands: RefCell<HashMap<(String, String, String), String>>,
+    parameters: RefCell<HashMap<(String, String), Vec<FormalParameter>>>,
+    steps: RefCell<HashMap<(String, String), Vec<Step>>>,
+    calls: RefCell<Vec<Call>>,
+}
+
+impl RecordingServer {
+    pub fn new() -> RecordingServer {
+        RecordingServer::default()
+    }
+
+    pub fn add_plugin(&mut self, key: &str, version: &str) {
+        let plugin = Plugin {
+            plugin_name: format!("{}-{}", key, version),
+            plugin_version: String::from(version),
+        };
+        self.plugins.insert(String::from(key), plugin);
+    }
+
+    pub fn calls(&self) -> Vec<Call> {
+        self.calls.borrow().clone()
+    }
+
+    pub fn property(&self, name: &str) -> Option<String> {
+        self.properties.borrow().get(name).cloned()
+    }
+
+    pub fn command(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Option<String> {
+        let key = (String::from(project_name), String::from(procedure_name), String::from(step_name));
+        self.commands.borrow().get(&key).cloned()
+    }
+
+    pub fn steps(&self, project_name: &str, procedure_name: &str) -> Vec<Step> {
+        let key = (String::from(project_name), String::from(procedure_name));
+        self.steps.borrow().get(&key).cloned().unwrap_or_default()
+    }
+
+    fn step_key(&self, project_name: &str, procedure_name: &str, step_name: &str) -> (String, String, String) {
+        (String::from(project_name), String::from(procedure_name), String::from(step_name))
+    }
+
+    pub fn formal_parameters(&self, project_name: &str, procedure_name: &str) -> Vec<FormalParameter> {
+        let key = (String::from(project_name), String::from(procedure_name));
+        self.parameters.borrow().get(&key).cloned().unwrap_or_default()
+    }
+}
+
+impl FlowServer for RecordingServer {
+    fn name(&self) -> String {
+        String::from("recording")
+    }
+
+    fn set_property(&self, name: &str, value: &str) -> Result<Property, Error> {
+        self.calls.borrow_mut().push(Call::SetProperty {
+            name: String::from(name),
+            value: String::from(value),
+        });
+        self.properties.borrow_mut().insert(String::from(name), String::from(value));
+        Ok(Property { property_name: Some(String::from(name)), value: String::from(value) })
+    }
+
+    fn get_property(&self, name: &str) -> Result<Property, Error> {
+        match self.property(name) {
+            Some(value) => Ok(Property { property_name: Some(String::from(name)), value }),
+            None => Err(Error::Http { status: 404, body: format!("Property {} is not found", name) })
+        }
+    }
+
+    fn delete_property(&self, name: &str) -> Result<(), Error> {
+        self.calls.borrow_mut().push(Call::DeleteProperty { name: String::from(name) });
+        match self.properties.borrow_mut().remove(name) {
+            Some(_) => Ok(()),
+            None => Err(Error::Http { status: 404, body: format!("Property {} is not found", name) })
+        }
+    }
+
+    fn set_procedure_command(&self, project_name: &str, procedure_name: &str, step_name: &str, command: &str) -> Result<(), Error> {
+        self.calls.borrow_mut().push(Call::SetProcedureCommand {
+            project: String::from(project_name),
+            procedure: String::from(procedure_name),
+            step: String::from(step_name),
+            command: String::from(command),
+        });
+        let key = (String::from(project_name), String::from(procedure_name), String::from(step_name));
+        self.commands.borrow_mut().insert(key, String::from(command));
+        Ok(())
+    }
+
+    fn get_procedure_command(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<String, Error> {
+        match self.command(project_name, procedure_name, step_name) {
+            Some(command) => Ok(command),
+            None => Err(Error::Http { status: 404, body: format!("Step {} is not found", step_name) })
+        }
+    }
+
+    fn get_step(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<Step, Error> {
+        match self.steps(project_name, procedure_name).into_iter().find(|s| s.step_name == step_name) {
+            Some(step) => Ok(step),
+            None => Err(Error::Http { status: 404, body: format!("Step {} is not found", step_name) })
+        }
+    }
+
+    fn set_step_attribute(&self, project_name: &str, procedure_name: &str, step_name: &str, attribute: &str, value: &str) -> Result<(), Error> {
+        let (project, procedure, step) = self.step_key(project_name, procedure_name, step_name);
+        self.calls.borrow_mut().push(Call::SetStepAttribute {
+            project, procedure, step,
+            attribute: String::from(attribute),
+            value: String::from(value),
+        });
+        let key = (String::from(project_name), String::from(procedure_name));
+        let mut steps = self.steps.borrow_mut();
+        let existing = steps.get_mut(&key).and_then(|list| list.iter_mut().find(|s| s.step_name == step_name));
+        match existing.and_then(|step| step.attribute_mut(attribute)) {
+            Some(current) => {
+                *current = Some(String::from(value));
+                Ok(())
+            },
+            None => Err(Error::Http { status: 404, body: format!("Step {} with {} is not found", step_name, attribute) })
+        }
+    }
+
+    fn get_steps(&self, project_name: &str, procedure_name: &str) -> Result<Vec<Step>, Error> {
+        Ok(self.steps(project_name, procedure_name))
+    }
+
+    fn create_step(&self, project_name: &str, procedure_name: &str, step: &Step) -> Result<(), Error> {
+        let (project, procedure, name) = self.step_key(project_name, procedure_name, &step.step_name);
+        self.calls.borrow_mut().push(Call::CreateStep { project, procedure, step: name });
+        if let Some(ref command) = step.command {
+            let key = self.step_key(project_name, procedure_name, &step.step_name);
+            self.commands.borrow_mut().insert(key, command.clone());
+        }
+        let key = (String::from(project_name), String::from(procedure_name));
+        self.steps.borrow_mut().entry(key).or_insert_with(Vec::new).push(step.clone());
+        Ok(())
+    }
+
+    fn modify_step(&self, project_name: &str, procedure_name: &str, step: &Step) -> Result<(), Error> {
+        let (project, procedure, name) = self.step_key(project_name, procedure_name, &step.step_name);
+        self.calls.borrow_mut().push(Call::ModifyStep { project, procedure, step: name });
+        if let Some(ref command) = step.command {
+            let key = self.step_key(project_name, procedure_name, &step.step_name);
+            self.commands.borrow_mut().insert(key, command.clone());
+        }
+        let key = (String::from(project_name), String::from(procedure_name));
+        let mut steps = self.steps.borrow_mut();
+        match steps.get_mut(&key).and_then(|list| list.iter_mut().find(|s| s.step_name == step.step_name)) {
+            Some(existing) => {
+                *existing = step.clone();
+                Ok(())
+            },
+            None => Err(Error::Http { status: 404, body: format!("Step {} is not found", step.step_name) })
+        }
+    }
+
+    fn delete_step(&self, project_name: &str, procedure_name: &str, step_name: &str) -> Result<(), Error> {
+        let (project, procedure, step) = self.step_key(project_name, procedure_name, step_name);
+        self.calls.borrow_mut().push(Call::DeleteStep { project, procedure, step });
+        let key = (String::from(project_name), String::from(procedure_name));
+        if let Some(list) = self.steps.borrow_mut().get_mut(&key) {
+            list.retain(|s| s.step_name != step_name);
+        }
+        self.commands.borrow_mut().remove(&self.step_key(project_name, procedure_name, step_name));
+        Ok(())
+    }
+
+    fn move_step(&self, project_name: &str, procedure_name: &str, step_name: &str, before_step: Option<&str>) -> Result<(), Error> {
+        let (project, procedure, step) = self.step_key(project_name, procedure_name, step_name);
+        self.calls.borrow_mut().push(Call::MoveStep { project, procedure, step, before: before_step.map(String::from) });
+        let key = (String::from(project_name), String::from(procedure_name));
+        let mut steps = self.steps.borrow_mut();
+        let list = steps.entry(key).or_insert_with(Vec::new);
+        let position = match list.iter().position(|s| s.step_name == step_name) {
+            Some(position) => position,
+            None => return Err(Error::Http { status: 404, body: format!("Step {} is not found", step_name) })
+        };
+        let step = list.remove(position);
+        let target = before_step
+            .and_then(|before| list.iter().position(|s| s.step_name == before))
+            .unwrap_or(list.len());
+        list.insert(target, step);
+        Ok(())
+    }
+
+    fn get_formal_parameters(&self, project_name: &str, procedure_name: &str) -> Result<Vec<FormalParameter>, Error> {
+        Ok(self.formal_parameters(project_name, procedure_name))
+    }
+
+    fn get_formal_parameter(&self, project_name: &str, procedure_name: &str, name: &str) -> Result<FormalParameter, Error> {
+        match self.formal_parameters(project_name, procedure_name).into_iter().find(|p| p.name == name) {
+            Some(parameter) => Ok(parameter),
+            None => Err(Error::Http { status: 404, body: format!("Formal parameter {} is not found", name) })
+        }
+    }
+
+    fn set_formal_parameter_attribute(&self, project_name: &str, procedure_name: &str, name: &str, attribute: &str, value: &str) -> Result<(), Error> {
+        self.calls.borrow_mut().push(Call::SetFormalParameterAttribute {
+            project: String::from(project_name),
+            procedure: String::from(procedure_name),
+            name: String::from(name),
+            attribute: String::from(attribute),
+            value: String::from(value),
+        });
+        let key = (String::from(project_name), String::from(procedure_name));
+        let mut parameters = self.parameters.borrow_mut();
+        let existing = parameters.get_mut(&key).and_then(|list| list.iter_mut().find(|p| p.name == name));
+        match existing.and_then(|parameter| parameter.attribute_mut(attribute)) {
+            Some(current) => {
+                *current = Some(String::from(value));
+                Ok(())
+            },
+            None => Err(Error::Http { status: 404, body: format!("Formal parameter {} with {} is not found", name, attribute) })
+        }
+    }
+
+    fn create_formal_parameter(&self, project_name: &str, procedure_name: &str, parameter: &FormalParameter) -> Result<(), Error> {
+        self.calls.borrow_mut().push(Call::CreateFormalParameter {
+            project: String::from(project_name),
+            procedure: String::from(procedure_name),
+            name: parameter.name.clone(),
+        });
+        let key = (String::from(project_name), String::from(procedure_name));
+        self.parameters.borrow_mut().entry(key).or_insert_with(Vec::new).push(parameter.clone());
+        Ok(())
+    }
+
+    fn modify_formal_parameter(&self, project_name: &str, procedure_name: &str, parameter: &FormalParameter) -> Result<(), Error> {
+        self.calls.borrow_mut().push(Call::ModifyFormalParameter {
+            project: String::from(project_name),
+            procedure: String::from(procedure_name),
+            name: parameter.name.clone(),
+        });
+        let key = (String::from(project_name), String::from(procedure_name));
+        let mut parameters = self.parameters.borrow_mut();
+        match parameters.get_mut(&key).and_then(|list| list.iter_mut().find(|p| p.name == parameter.name)) {
+            Some(existing) => {
+                *existing = parameter.clone();
+                Ok(())
+            },
+            None => Err(Error::Http { status: 404, body: format!("Formal parameter {} is not found", parameter.name) })
+        }
+    }
+
+    fn delete_formal_parameter(&self, project_name: &str, procedure_name: &str, name: &str) -> Result<(), Error> {
+        self.calls.borrow_mut().push(Call::DeleteFormalParameter {
+            project: String::from(project_name),
+            procedure: String::from(procedure_name),
+            name: String::from(name),
+        });
+        let key = (String::from(project_name), String::from(procedure_name));
+        if let Some(list) = self.parameters.borrow_mut().get_mut(&key) {
+            list.retain(|p| p.name != name);
+        }
+        Ok(())
+    }
+
+    fn eval_dsl(&self, dsl: &str) -> Result<(), Error> {
+        self.calls.borrow_mut().push(Call::EvalDsl { dsl: String::from(dsl) });
+        Ok(())
+    }
+
+    fn get_plugin(&self, plugin_name: &str) -> Result<Plugin, Error> {
+        match self.plugins.get(plugin_name) {
+            Some(plugin) => Ok(plugin.clone()),
+            None => Err(Error::Http { status: 404, body: format!("Plugin {} is not found", plugin_name) })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_calls() {
+        let mut server = RecordingServer::new();
+        server.add_plugin("EC-Test", "1.0.0");
+        let plugin = server.get_plugin("EC-Test").unwrap();
+        assert_eq!(plugin.plugin_name, "EC-Test-1.0.0");
+
+        server.set_property("/plugins/EC-Test/project/ec_setup", "setup").unwrap();
+        server.set_procedure_command(&plugin.plugin_name, "Deploy", "deploy", "echo").unwrap();
+        assert_eq!(server.property("/plugins/EC-Test/project/ec_setup"), Some(String::from("setup")));
+        assert_eq!(server.command("EC-Test-1.0.0", "Deploy", "deploy"), Some(String::from("echo")));
+        assert_eq!(server.calls().len(), 2);
+        assert!(server.get_property("/plugins/EC-Test/project/missing").unwrap_err().is_not_found());
+    }
+}
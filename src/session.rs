@@ -0,0 +1,186 @@
+use std::env;
+use std::fs::{self, File, OpenOptions};
+#[cfg(unix)]
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use regex::Regex;
+use serde_xml_rs::from_reader;
+use serde_xml_rs;
+use url::Url;
+use error::Error;
+
+const SESSION_FILES: [&str; 2] = [".ecsession", ".cfsession"];
+
+#[derive(Deserialize, Debug)]
+struct Session {
+    url: String,
+    #[serde(rename="sessionId", default)]
+    session_id: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Sessions {
+    #[serde(rename = "session", default)]
+    sessions: Vec<Session>
+}
+
+pub fn session_file() -> Option<PathBuf> {
+    let home = env::home_dir()?;
+    SESSION_FILES.iter().map(|name| home.join(name)).find(|path| path.exists())
+}
+
+pub fn default_session_file() -> Option<PathBuf> {
+    session_file().or_else(|| env::home_dir().map(|home| home.join(SESSION_FILES[0])))
+}
+
+fn server_host(server: &str) -> Option<String> {
+    if server.contains("://") {
+        Url::parse(server).ok().and_then(|url| url.host_str().map(String::from))
+    } else {
+        server.split(|c| c == ':' || c == '/').next().map(String::from)
+    }
+}
+
+fn same_host(url: &str, server: &str) -> bool {
+    match Url::parse(url) {
+        Ok(url) => url.host_str().is_some() && url.host_str().map(String::from) == server_host(server),
+        Err(_) => false
+    }
+}
+
+fn read_file(path: &Path) -> Result<String, Error> {
+    let mut file = File::open(path)?;
+    let mut contents = String::new();
+    file.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+pub fn read_sid(path: &Path, server: &str) -> Option<String> {
+    let contents = read_file(path).ok()?;
+    let sessions: Result<Sessions, serde_xml_rs::Error> = from_reader(contents.as_bytes());
+    match sessions {
+        Ok(s) => {
+            for session in s.sessions {
+                if same_host(&session.url, server) {
+                    return Some(session.session_id)
+                }
+            }
+            None
+        },
+        Err(_) => { None }
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;")
+}
+
+pub fn write_sid(path: &Path, url: &str, sid: &str) -> Result<(), Error> {
+    let server = match Url::parse(url) {
+        Ok(u) => u.host_str().map(String::from),
+        Err(e) => return Err(Error::Metadata(format!("Invalid server url {}: {}", url, e)))
+    };
+    let server = server.unwrap_or_default();
+    let contents = if path.exists() { read_file(path)? } else { String::new() };
+
+//    Sessions are copied as they are, so fields this tool does not know about are kept
+    let session_re = Regex::new("(?s)<session>.*?</session>").unwrap();
+    let url_re = Regex::new("(?s)<url>(.*?)</url>").unwrap();
+    let mut sessions: Vec<String> = session_re.find_iter(&contents)
+        .map(|m| String::from(m.as_str()))
+        .filter(|session| {
+            match url_re.captures(session) {
+                Some(caps) => !same_host(caps.get(1).unwrap().as_str().trim(), &server),
+                None => true
+            }
+        })
+        .collect();
+    sessions.push(format!("<session>\n    <url>{}</url>\n    <sessionId>{}</sessionId>\n  </session>", escape(url), escape(sid)));
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sessions>\n");
+    for session in sessions {
+        xml.push_str("  ");
+        xml.push_str(&session);
+        xml.push_str("\n");
+    }
+    xml.push_str("</sessions>\n");
+
+    let mut file = open_private(path)?;
+    file.write_all(xml.as_bytes())?;
+    Ok(())
+}
+
+//    The session id is as good as a password, so only the owner may read the file
+#[cfg(unix)]
+fn open_private(path: &Path) -> Result<File, Error> {
+    let file = OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(file)
+}
+
+#[cfg(not(unix))]
+fn open_private(path: &Path) -> Result<File, Error> {
+    Ok(OpenOptions::new().write(true).create(true).truncate(true).open(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn session_path(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("rusty-sentry-{}.xml", name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn write_new_session_file() {
+        let path = session_path("new-session");
+        write_sid(&path, "https://flow:443/", "abc").unwrap();
+        assert_eq!(read_sid(&path, "flow"), Some(String::from("abc")));
+        assert_eq!(read_sid(&path, "https://flow:8443/commander"), Some(String::from("abc")));
+        assert_eq!(read_sid(&path, "flow:8000"), Some(String::from("abc")));
+        assert_eq!(read_sid(&path, "other"), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn replace_existing_session() {
+        let path = session_path("replace-session");
+        let mut file = File::create(&path).unwrap();
+        file.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<sessions>
+  <session>
+    <url>https://flow:8443/</url>
+    <sessionId>old</sessionId>
+  </session>
+  <session>
+    <url>https://staging:8443/</url>
+    <sessionId>staging-sid</sessionId>
+    <userName>admin</userName>
+  </session>
+</sessions>
+").unwrap();
+
+        write_sid(&path, "https://flow:443/", "new").unwrap();
+        assert_eq!(read_sid(&path, "flow"), Some(String::from("new")));
+        assert_eq!(read_sid(&path, "staging"), Some(String::from("staging-sid")));
+        let contents = read_file(&path).unwrap();
+        assert!(contents.contains("<userName>admin</userName>"));
+        assert!(!contents.contains("old"));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn session_file_is_private() {
+        let path = session_path("private-session");
+        File::create(&path).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_sid(&path, "https://flow:443/", "abc").unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use serde_json;
+use error::Error;
+
+const STATE_FOLDER: &str = "rusty-sentry";
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+//    FNV-1a, stable between runs and releases unlike the std hasher
+pub fn fingerprint(value: &str) -> String {
+    let mut hash = FNV_OFFSET;
+    for byte in value.as_bytes() {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    format!("{:016x}", hash)
+}
+
+//    Hashes of the values uploaded by this process, editors often save the same content repeatedly
+#[derive(Debug, Default)]
+pub struct UploadCache {
+    hashes: RefCell<HashMap<String, String>>,
+}
+
+impl UploadCache {
+    pub fn is_unchanged(&self, key: &str, value: &str) -> bool {
+        self.hashes.borrow().get(key).map_or(false, |hash| *hash == fingerprint(value))
+    }
+
+    pub fn store(&self, key: &str, value: &str) {
+        self.hashes.borrow_mut().insert(String::from(key), fingerprint(value));
+    }
+
+    pub fn forget(&self, key: &str) {
+        self.hashes.borrow_mut().remove(key);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncState {
+    path: PathBuf,
+}
+
+impl SyncState {
+    pub fn new(path: &Path) -> SyncState {
+        SyncState { path: path.to_path_buf() }
+    }
+
+//    Kept in the user's cache dir so pushes do not dirty the plugin's working tree
+    pub fn for_plugin(folder: &Path) -> SyncState {
+        let cache_home = match env::var("XDG_CACHE_HOME") {
+            Ok(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => env::home_dir().map_or_else(env::temp_dir, |home| home.join(".cache")),
+        };
+        let folder = fs::canonicalize(folder).unwrap_or_else(|_| folder.to_path_buf());
+        let name = folder.file_name().map_or(String::new(), |name| name.to_string_lossy().into_owned());
+        let file = format!("{}-{}.json", name, fingerprint(&folder.to_string_lossy()));
+        SyncState::new(&cache_home.join(STATE_FOLDER).join("state").join(file))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn read(&self) -> Result<BTreeMap<String, String>, Error> {
+        let mut contents = String::new();
+        match File::open(&self.path) {
+            Ok(mut f) => f.read_to_string(&mut contents)?,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        match serde_json::from_str(&contents) {
+            Ok(entries) => Ok(entries),
+            Err(e) => Err(Error::Config(format!("Cannot parse state file {}: {}", self.path.display(), e)))
+        }
+    }
+
+    pub fn last_synced(&self, key: &str) -> Result<Option<String>, Error> {
+        Ok(self.read()?.remove(key))
+    }
+
+//    The file is read again before every write, so updaters for several servers can share it
+    pub fn record(&self, key: &str, value: &str) -> Result<(), Error> {
+        let mut entries = self.read()?;
+        entries.insert(String::from(key), fingerprint(value));
+        let contents = serde_json::to_string_pretty(&entries)?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut f = File::create(&self.path)?;
+        f.write_all(contents.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn stable_fingerprint() {
+        assert_eq!(fingerprint(""), "cbf29ce484222325");
+        assert_eq!(fingerprint("a"), "af63dc4c8601ec8c");
+        assert!(fingerprint("print 1") != fingerprint("print 2"));
+    }
+
+    #[test]
+    fn upload_cache() {
+        let cache = UploadCache::default();
+        assert!(!cache.is_unchanged("flow property /a", "first"));
+        cache.store("flow property /a", "first");
+        assert!(cache.is_unchanged("flow property /a", "first"));
+        assert!(!cache.is_unchanged("flow property /a", "second"));
+        assert!(!cache.is_unchanged("other property /a", "first"));
+    }
+
+    #[test]
+    fn record_and_read() {
+        let path = env::temp_dir().join("rusty-sentry-state-test.json");
+        let _ = fs::remove_file(&path);
+        let state = SyncState::new(&path);
+        assert_eq!(state.last_synced("flow property /a").unwrap(), None);
+
+        state.record("flow property /a", "first").unwrap();
+        state.record("flow property /b", "second").unwrap();
+        assert_eq!(state.last_synced("flow property /a").unwrap(), Some(fingerprint("first")));
+        assert_eq!(SyncState::new(&path).last_synced("flow property /b").unwrap(), Some(fingerprint("second")));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn state_outside_plugin() {
+        let folder = env::temp_dir().join("rusty-sentry-state-plugin");
+        let state = SyncState::for_plugin(&folder);
+        assert!(!state.path().starts_with(&folder));
+        assert_eq!(state.path(), SyncState::for_plugin(&folder).path());
+        assert!(state.path() != SyncState::for_plugin(&env::temp_dir().join("other")).path());
+    }
+}
@@ -0,0 +1,340 @@
+use std::path::{Path, PathBuf};
+use error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use updater::{PartialUpdate, UpdateResult, Mutation, restore_placeholders};
+
+const IGNORED_FOLDERS: [&str; 2] = ["build", "out"];
+
+#[derive(Debug, Default)]
+pub struct PushSummary {
+    pub updated: Vec<PathBuf>,
+    pub recorded: Vec<(PathBuf, Mutation)>,
+    pub unchanged: Vec<PathBuf>,
+    pub skipped: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+#[derive(Debug, Default)]
+pub struct CompareSummary {
+    pub in_sync: Vec<(PathBuf, Mutation)>,
+    pub changed: Vec<(PathBuf, Mutation, String)>,
+    pub missing: Vec<(PathBuf, Mutation)>,
+    pub skipped: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+impl CompareSummary {
+    pub fn differing(&self) -> Vec<&PathBuf> {
+        let changed = self.changed.iter().map(|&(ref file, _, _)| file);
+        let missing = self.missing.iter().map(|&(ref file, _)| file);
+        changed.chain(missing).collect()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PullSummary {
+    pub pulled: Vec<PathBuf>,
+    pub recorded: Vec<PathBuf>,
+    pub in_sync: Vec<PathBuf>,
+    pub missing: Vec<PathBuf>,
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+pub fn plugin_files(folder: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    collect_files(folder, folder, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_files(root: &Path, folder: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    for entry in fs::read_dir(folder)? {
+        let path = entry?.path();
+        let hidden = path.file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.starts_with("."));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+//            Gradle build output contains copies of the mapped files
+            let ignored = folder == root && IGNORED_FOLDERS.iter().any(|name| path.ends_with(name));
+            if !ignored {
+                collect_files(root, &path, files)?;
+            }
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+pub fn push<T>(plugin: &T) -> Result<PushSummary, Error> where T: PartialUpdate {
+    let mut summary = PushSummary::default();
+    for file in plugin_files(plugin.meta().folder())? {
+        match plugin.update(&file) {
+            Ok(UpdateResult::Updated(_)) => summary.updated.push(file),
+            Ok(UpdateResult::Recorded(mutation)) => summary.recorded.push((file, mutation)),
+            Ok(UpdateResult::Unchanged(_)) => summary.unchanged.push(file),
+            Ok(UpdateResult::Deleted(_)) | Ok(UpdateResult::Skipped) => summary.skipped.push(file),
+            Err(e) => summary.failed.push((file, e)),
+        }
+    }
+    Ok(summary)
+}
+
+//    Comparing only reads, the sync commands decide which server values become the base for conflict checks
+pub fn record_in_sync<T>(plugin: &T, summary: &CompareSummary) where T: PartialUpdate {
+    for &(_, ref mutation) in summary.in_sync.iter() {
+        plugin.record_synced(&mutation.target, &mutation.value);
+    }
+}
+
+//    Local values are compared after the placeholders are substituted, as they would be pushed
+pub fn compare<T>(plugin: &T) -> Result<CompareSummary, Error> where T: PartialUpdate {
+    let mut summary = CompareSummary::default();
+    for file in plugin_files(plugin.meta().folder())? {
+        let mutation = match plugin.resolve(&file) {
+            Ok(Some(mutation)) => mutation,
+            Ok(None) => {
+                summary.skipped.push(file);
+                continue;
+            },
+            Err(e) => {
+                summary.failed.push((file, e));
+                continue;
+            }
+        };
+        match plugin.fetch(&mutation.target) {
+            Ok(Some(ref remote)) if *remote == mutation.value => summary.in_sync.push((file, mutation)),
+            Ok(Some(remote)) => summary.changed.push((file, mutation, remote)),
+            Ok(None) => summary.missing.push((file, mutation)),
+            Err(Error::Mapping(_)) => summary.skipped.push(file),
+            Err(e) => summary.failed.push((file, e)),
+        }
+    }
+    Ok(summary)
+}
+
+pub fn pull<T>(plugin: &T) -> Result<PullSummary, Error> where T: PartialUpdate {
+    let compared = compare(plugin)?;
+    if !plugin.options().dry_run {
+        record_in_sync(plugin, &compared);
+    }
+    let mut summary = PullSummary {
+        in_sync: compared.in_sync.into_iter().map(|(file, _)| file).collect(),
+        missing: compared.missing.into_iter().map(|(file, _)| file).collect(),
+        failed: compared.failed,
+        ..PullSummary::default()
+    };
+    for (file, mutation, remote) in compared.changed {
+        if plugin.options().dry_run {
+            println!("[dry-run] write {}", file.display());
+            summary.recorded.push(file);
+            continue;
+        }
+        match write_pulled(plugin, &file, &remote) {
+            Ok(_) => {
+                plugin.record_synced(&mutation.target, &remote);
+                summary.pulled.push(file)
+            },
+            Err(e) => summary.failed.push((file, e)),
+        }
+    }
+    Ok(summary)
+}
+
+fn write_pulled<T>(plugin: &T, file: &Path, remote: &str) -> Result<(), Error> where T: PartialUpdate {
+    let mut template = String::new();
+    File::open(file)?.read_to_string(&mut template)?;
+    let contents = restore_placeholders(plugin.meta(), &template, remote);
+    let mut f = File::create(file)?;
+    f.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use updater::{PluginWizard, Target, UpdateOptions};
+    use std::env;
+    use server::{FlowServer, RecordingServer};
+    use state::SyncState;
+
+    fn options() -> UpdateOptions {
+        UpdateOptions {
+            keep_extensions: false,
+            dry_run: false,
+            check_conflicts: false,
+            allow_delete: false,
+            eval_dsl: false,
+        }
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+    }
+
+    fn server() -> RecordingServer {
+        let mut server = RecordingServer::new();
+        server.add_plugin("EC-Wizard", "1.2.0");
+        server
+    }
+
+//    A copy of the fixture the test can change, with its sync state next to it instead of in the user's cache
+    fn temp_plugin(test: &str, options: UpdateOptions) -> (PathBuf, PluginWizard<RecordingServer>) {
+        let root = env::temp_dir().join(format!("rusty-sentry-{}-test", test));
+        let _ = fs::remove_dir_all(&root);
+        let folder = root.join("EC-Wizard");
+        copy_folder(&fixture("EC-Wizard"), &folder);
+        let mut plugin = PluginWizard::build(&folder, server(), options).unwrap();
+        plugin.set_sync_state(SyncState::new(&root.join("state.json")));
+        (folder, plugin)
+    }
+
+    #[test]
+    fn push_wizard_plugin() {
+        let folder = fixture("EC-Wizard");
+        let plugin = PluginWizard::build(&folder, server(), options()).unwrap();
+
+        let summary = push(&plugin).unwrap();
+        assert_eq!(summary.updated.len(), 6);
+        assert!(summary.failed.is_empty());
+        assert!(summary.skipped.contains(&folder.join("README.md")));
+        assert_eq!(plugin.server().formal_parameters("EC-Wizard-1.2.0", "Deploy").len(), 2);
+        assert_eq!(plugin.server().steps("EC-Wizard-1.2.0", "Deploy").len(), 2);
+        let calls = plugin.server().calls().len();
+
+        let summary = push(&plugin).unwrap();
+        assert_eq!(summary.unchanged.len(), 6);
+        assert_eq!(plugin.server().calls().len(), calls);
+    }
+
+    #[test]
+    fn compare_wizard_plugin() {
+        let folder = fixture("EC-Wizard");
+        let plugin = PluginWizard::build(&folder, server(), options()).unwrap();
+
+        let summary = compare(&plugin).unwrap();
+        assert_eq!(summary.missing.len(), 5);
+        assert!(summary.skipped.contains(&folder.join("README.md")));
+        assert!(summary.skipped.contains(&folder.join("dsl/procedures/Deploy/procedure.dsl")));
+
+        push(&plugin).unwrap();
+        plugin.server().set_property("/plugins/EC-Wizard/project/ec_setup", "changed on the server").unwrap();
+        let summary = compare(&plugin).unwrap();
+        assert_eq!(summary.in_sync.len(), 4);
+        assert_eq!(summary.differing(), vec![&folder.join("dsl/properties/ec_setup.pl")]);
+        assert_eq!(summary.changed[0].2, "changed on the server");
+        assert!(summary.failed.is_empty());
+    }
+
+    fn copy_folder(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap() {
+            let path = entry.unwrap().path();
+            let target = to.join(path.file_name().unwrap());
+            if path.is_dir() {
+                copy_folder(&path, &target);
+            } else {
+                fs::copy(&path, &target).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn pull_wizard_plugin() {
+        let (folder, plugin) = temp_plugin("pull", options());
+        push(&plugin).unwrap();
+
+        let file = folder.join("dsl/procedures/Deploy/steps/setup.pl");
+        let command = plugin.server().command("EC-Wizard-1.2.0", "Deploy", "setup").unwrap();
+        let edited = format!("{}print \"Hot fix\\n\";\n", command);
+        plugin.server().set_procedure_command("EC-Wizard-1.2.0", "Deploy", "setup", &edited).unwrap();
+
+        let summary = pull(&plugin).unwrap();
+        assert_eq!(summary.pulled, vec![file.clone()]);
+        assert_eq!(summary.in_sync.len(), 4);
+
+        let mut contents = String::new();
+        File::open(&file).unwrap().read_to_string(&mut contents).unwrap();
+        assert!(contents.contains("@PLUGIN_NAME@"));
+        assert!(contents.ends_with("print \"Hot fix\\n\";\n"));
+        assert!(compare(&plugin).unwrap().differing().is_empty());
+        fs::remove_dir_all(folder.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unmapped_step_is_not_in_sync() {
+        let (folder, plugin) = temp_plugin("unmapped", options());
+        fs::create_dir_all(folder.join("dsl/procedures/Broken/steps")).unwrap();
+        fs::write(folder.join("dsl/procedures/Broken/procedure.dsl"), "// not written yet\n").unwrap();
+        fs::write(folder.join("dsl/procedures/Broken/steps/cleanup.pl"), "print 'cleanup';\n").unwrap();
+        let summary = compare(&plugin).unwrap();
+        let failed: Vec<&PathBuf> = summary.failed.iter().map(|&(ref file, _)| file).collect();
+        assert_eq!(failed, vec![&folder.join("dsl/procedures/Broken/procedure.dsl"), &folder.join("dsl/procedures/Broken/steps/cleanup.pl")]);
+        fs::remove_dir_all(folder.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn compare_with_missing_plugin() {
+        let plugin = PluginWizard::build(&fixture("EC-Wizard"), RecordingServer::new(), options()).unwrap();
+
+        let summary = compare(&plugin).unwrap();
+        assert!(!summary.failed.is_empty());
+        for &(_, ref e) in summary.failed.iter() {
+            assert_eq!(e.to_string(), "Plugin EC-Wizard is not installed on recording");
+        }
+    }
+
+    #[test]
+    fn conflicting_push_is_refused() {
+        let (folder, plugin) = temp_plugin("conflict", UpdateOptions { check_conflicts: true, ..options() });
+        let file = folder.join("dsl/properties/ec_setup.pl");
+        plugin.update(&file).unwrap();
+        assert!(plugin.sync_state().path().exists());
+
+        plugin.server().set_property("/plugins/EC-Wizard/project/ec_setup", "hot fix").unwrap();
+        fs::write(&file, "print 'local change';\n").unwrap();
+        match plugin.update(&file) {
+            Err(Error::Conflict(_)) => {},
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert_eq!(plugin.server().property("/plugins/EC-Wizard/project/ec_setup"), Some(String::from("hot fix")));
+
+//        Pulling the hot fix makes it the new base
+        pull(&plugin).unwrap();
+        fs::write(&file, "print 'local change';\n").unwrap();
+        plugin.update(&file).unwrap();
+        assert_eq!(plugin.server().property("/plugins/EC-Wizard/project/ec_setup"), Some(String::from("print 'local change';\n")));
+        fs::remove_dir_all(folder.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn unrecorded_difference_is_a_conflict() {
+        let (folder, plugin) = temp_plugin("unrecorded", UpdateOptions { check_conflicts: true, ..options() });
+        let state = plugin.sync_state();
+        plugin.server().set_property("/plugins/EC-Wizard/project/ec_setup", "hot fix").unwrap();
+        let file = folder.join("dsl/properties/ec_setup.pl");
+        match plugin.update(&file) {
+            Err(Error::Conflict(_)) => {},
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+//        Comparing does not make the server value the base
+        assert_eq!(compare(&plugin).unwrap().changed.len(), 1);
+        assert!(!state.path().exists());
+        match plugin.update(&file) {
+            Err(Error::Conflict(_)) => {},
+            other => panic!("Unexpected result: {:?}", other),
+        }
+
+//        A confirmed overwrite skips the check and records the pushed value
+        plugin.update_with(&file, true).unwrap();
+        assert!(plugin.server().property("/plugins/EC-Wizard/project/ec_setup").unwrap().contains("Setting up $pluginKey"));
+        assert!(state.last_synced(&plugin.state_key(&Target::Property(String::from("/plugins/EC-Wizard/project/ec_setup")))).unwrap().is_some());
+        fs::remove_dir_all(folder.parent().unwrap()).unwrap();
+    }
+}
@@ -1,11 +1,20 @@
 use std::path::{Path, PathBuf};
 use regex::{Regex, escape};
 use std::path;
-use std::io::{Error, ErrorKind};
+use std::fmt;
+use std::io;
+use error::Error;
+use std::fs;
 use std::fs::File;
 use std::io::prelude::*;
-use serde_xml_rs::deserialize;
-use ef_client::EFClient;
+use serde_xml_rs::from_reader;
+use ef_client::{self, EFClient, Step, FormalParameter};
+use server::FlowServer;
+use state::{SyncState, UploadCache, fingerprint};
+use form;
+use dsl;
+use diff;
+use xpath::{self, XPathTarget};
 use serde_xml_rs;
 
 #[derive(Debug, Deserialize, PartialEq)]
@@ -58,30 +67,315 @@ pub struct PluginMeta {
     folder: PathBuf,
 }
 
+impl PluginMeta {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn folder(&self) -> &Path {
+        &self.folder
+    }
+}
+
 #[derive(Debug)]
-pub struct PluginWizard {
+pub struct PluginWizard<S: FlowServer = EFClient> {
     meta: PluginMeta,
-    ef_client: EFClient,
+    server: S,
     update_options: UpdateOptions,
+    uploads: UploadCache,
+    sync_state: SyncState,
 }
 
 #[derive(Debug)]
-pub struct PluginGradle {
+pub struct PluginGradle<S: FlowServer = EFClient> {
     meta: PluginMeta,
     manifest_path: PathBuf,
-    ef_client: EFClient,
+    server: S,
     update_options: UpdateOptions,
+    uploads: UploadCache,
+    sync_state: SyncState,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UpdateOptions {
-    pub keep_extensions: bool
+    pub keep_extensions: bool,
+    pub dry_run: bool,
+    pub check_conflicts: bool,
+    pub allow_delete: bool,
+    pub eval_dsl: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    Property(String),
+    StepCommand { procedure: String, step: String },
+    ProcedureForm { procedure: String },
+    ProcedureSteps { procedure: String },
+    Dsl { file: String },
+    StepAttribute { procedure: String, step: String, attribute: String },
+    FormalParameter { procedure: String, parameter: String, attribute: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mutation {
+    pub target: Target,
+    pub value: String,
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Target::Property(ref name) => write!(f, "property {}", name),
+            Target::StepCommand { ref procedure, ref step } => write!(f, "procedure '{}', step '{}'", procedure, step),
+            Target::ProcedureForm { ref procedure } => write!(f, "form of procedure '{}'", procedure),
+            Target::ProcedureSteps { ref procedure } => write!(f, "steps of procedure '{}'", procedure),
+            Target::Dsl { ref file } => write!(f, "DSL {}", file),
+            Target::StepAttribute { ref procedure, ref step, ref attribute } =>
+                write!(f, "{} of procedure '{}', step '{}'", attribute, procedure, step),
+            Target::FormalParameter { ref procedure, ref parameter, ref attribute } =>
+                write!(f, "{} of parameter '{}' of procedure '{}'", attribute, parameter, procedure),
+        }
+    }
+}
+
+impl fmt::Display for Mutation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.target {
+            Target::Property(ref name) => write!(f, "set property {} ({} bytes)", name, self.value.len()),
+            Target::StepCommand { ref procedure, ref step } =>
+                write!(f, "set command of procedure '{}', step '{}' ({} bytes)", procedure, step, self.value.len()),
+            Target::ProcedureForm { ref procedure } =>
+                write!(f, "set form of procedure '{}' ({} bytes)", procedure, self.value.len()),
+            Target::ProcedureSteps { ref procedure } =>
+                write!(f, "sync steps of procedure '{}' ({} bytes)", procedure, self.value.len()),
+            Target::Dsl { ref file } => write!(f, "evaluate DSL {} ({} bytes)", file, self.value.len()),
+            Target::StepAttribute { .. } | Target::FormalParameter { .. } =>
+                write!(f, "set {} ({} bytes)", self.target, self.value.len()),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum UpdateResult {
+    Updated(Mutation),
+    Recorded(Mutation),
+    Unchanged(Mutation),
+    Deleted(Target),
+    Skipped,
 }
 
 pub trait PartialUpdate {
     type PluginType;
-    fn update(&self, file: &PathBuf) -> Result<(), Error>;
-    fn build(plugin_folder: &PathBuf, ef_client: EFClient, options: UpdateOptions) -> Result<Self::PluginType, Error>;
+    type Server: FlowServer;
+    fn build(plugin_folder: &PathBuf, server: Self::Server, options: UpdateOptions) -> Result<Self::PluginType, Error>;
+    fn resolve_target(&self, file: &PathBuf) -> Result<Option<Target>, Error>;
+    fn meta(&self) -> &PluginMeta;
+    fn server(&self) -> &Self::Server;
+    fn options(&self) -> &UpdateOptions;
+    fn uploads(&self) -> &UploadCache;
+    fn sync_state(&self) -> &SyncState;
+
+    fn resolve(&self, file: &PathBuf) -> Result<Option<Mutation>, Error> {
+        match self.resolve_target(file)? {
+            Some(target) => {
+                let value = self.get_file_content(file, self.meta())?;
+                Ok(Some(Mutation { target, value }))
+            },
+            None => Ok(None)
+        }
+    }
+
+    fn update(&self, file: &PathBuf) -> Result<UpdateResult, Error> {
+        self.update_with(file, false)
+    }
+
+//    A forced update overwrites the server value without checking it for conflicts
+    fn update_with(&self, file: &PathBuf, force: bool) -> Result<UpdateResult, Error> {
+        let mutation = match self.resolve(file)? {
+            Some(m) => m,
+            None => return Ok(UpdateResult::Skipped),
+        };
+        if self.options().dry_run {
+            println!("[dry-run] {}", mutation);
+            return Ok(UpdateResult::Recorded(mutation));
+        }
+        let key = self.state_key(&mutation.target);
+        if self.uploads().is_unchanged(&key, &mutation.value) {
+            println!("{} is unchanged", mutation.target);
+            return Ok(UpdateResult::Unchanged(mutation));
+        }
+        if self.options().check_conflicts && !force {
+            self.check_conflict(&mutation)?;
+        }
+        self.apply(&mutation)?;
+        self.record_synced(&mutation.target, &mutation.value);
+        Ok(UpdateResult::Updated(mutation))
+    }
+
+//    Only properties are deleted, steps belong to the procedure definition
+    fn remove(&self, file: &PathBuf) -> Result<UpdateResult, Error> {
+        let target = match self.resolve_target(file)? {
+            Some(target) => target,
+            None => return Ok(UpdateResult::Skipped),
+        };
+        let name = match target {
+            Target::Property(ref name) => name.clone(),
+            _ => return Err(Error::Mapping(format!("{} is not deleted from the server", target))),
+        };
+        if !self.options().allow_delete {
+            println!("Keeping {} on the server, use --allow-delete to delete it", target);
+            return Ok(UpdateResult::Skipped);
+        }
+        if self.options().dry_run {
+            println!("[dry-run] delete {}", target);
+            return Ok(UpdateResult::Skipped);
+        }
+        match self.server().delete_property(&name) {
+            Err(ref e) if e.is_not_found() => {},
+            Err(e) => return Err(e),
+            Ok(_) => println!("Deleted {}", target),
+        }
+        self.uploads().forget(&self.state_key(&target));
+        Ok(UpdateResult::Deleted(target))
+    }
+
+    fn state_key(&self, target: &Target) -> String {
+        format!("{} {}", self.server().name(), target)
+    }
+
+//    A server value that differs from the last pushed or pulled one was edited by someone else
+    fn check_conflict(&self, mutation: &Mutation) -> Result<(), Error> {
+        let remote = match self.fetch(&mutation.target) {
+            Ok(Some(remote)) => remote,
+            Ok(None) | Err(Error::Mapping(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        if remote == mutation.value {
+            return Ok(());
+        }
+        match self.sync_state().last_synced(&self.state_key(&mutation.target))? {
+            Some(ref synced) if *synced == fingerprint(&remote) => Ok(()),
+            Some(_) => Err(Error::Conflict(format!(
+                "{} was changed on {} since it was last pushed or pulled, use diff or pull to review it",
+                mutation.target, self.server().name()))),
+            None => Err(Error::Conflict(format!(
+                "{} differs on {} and was never pushed or pulled from here, use diff or pull to review it",
+                mutation.target, self.server().name()))),
+        }
+    }
+
+//    Called with every value known to be on the server after a push or pull
+    fn record_synced(&self, target: &Target, value: &str) {
+        self.uploads().store(&self.state_key(target), value);
+        if !self.options().check_conflicts {
+            return;
+        }
+        if let Err(e) = self.sync_state().record(&self.state_key(target), value) {
+            eprintln!("Cannot record sync state: {}", e);
+        }
+    }
+
+    fn apply(&self, mutation: &Mutation) -> Result<(), Error> {
+        match mutation.target {
+            Target::Property(ref name) => {
+                println!("Property name: {}", name);
+                self.server().set_property(name, &mutation.value)?;
+            },
+            Target::StepCommand { ref procedure, ref step } => {
+                let plugin = self.installed_plugin()?;
+                println!("Procedure name: {}, step name: {}", procedure, step);
+                self.server().set_procedure_command(&plugin.plugin_name, procedure, step, &mutation.value)?;
+                println!("Updated step");
+            },
+            Target::ProcedureForm { ref procedure } => {
+                let elements = form::parse(&mutation.value)?;
+                let plugin = self.installed_plugin()?;
+                self.server().set_property(&form::form_property(self.meta().key(), procedure), &mutation.value)?;
+                form::reconcile(self.server(), self.meta().key(), &plugin.plugin_name, procedure, &elements, self.options().allow_delete)?;
+                println!("Updated form of procedure {}", procedure);
+            },
+            Target::ProcedureSteps { ref procedure } => {
+                let mut steps = Vec::new();
+                for step in dsl::parse_steps(&mutation.value)? {
+                    let command = match step.command_file {
+                        Some(ref file) => Some(self.get_file_content(&self.meta().folder().join(file), self.meta())?),
+                        None => None,
+                    };
+                    steps.push(step.to_step(command));
+                }
+                let plugin = self.installed_plugin()?;
+                dsl::reconcile(self.server(), &plugin.plugin_name, procedure, &steps, self.options().allow_delete)?;
+                println!("Updated steps of procedure {}", procedure);
+            },
+            Target::Dsl { ref file } => {
+                let plugin = self.installed_plugin()?;
+                let folder = self.meta().folder();
+                let script = dsl::script(&mutation.value, self.meta().key(), &plugin.plugin_name, |path| {
+                    self.get_file_content(&folder.join(path), self.meta())
+                })?;
+                self.server().eval_dsl(&script.text).map_err(|e| dsl::script_error(file, &script, e))?;
+                println!("Evaluated {}", file);
+            },
+            Target::StepAttribute { ref procedure, ref step, ref attribute } => {
+                let plugin = self.installed_plugin()?;
+                self.server().set_step_attribute(&plugin.plugin_name, procedure, step, attribute, &mutation.value)?;
+                println!("Updated {}", mutation.target);
+            },
+            Target::FormalParameter { ref procedure, ref parameter, ref attribute } => {
+                let plugin = self.installed_plugin()?;
+                self.server().set_formal_parameter_attribute(&plugin.plugin_name, procedure, parameter, attribute, &mutation.value)?;
+                println!("Updated {}", mutation.target);
+            }
+        }
+        Ok(())
+    }
+
+//    None means the target does not exist on the server yet
+//    A missing plugin is an error, only a missing object inside it counts as not pushed yet
+    fn installed_plugin(&self) -> Result<ef_client::Plugin, Error> {
+        match self.server().get_plugin(self.meta().key()) {
+            Err(ref e) if e.is_not_found() => Err(Error::Metadata(format!(
+                "Plugin {} is not installed on {}", self.meta().key(), self.server().name()))),
+            other => other
+        }
+    }
+
+    fn fetch(&self, target: &Target) -> Result<Option<String>, Error> {
+        let result = match *target {
+            Target::Property(ref name) => self.server().get_property(name).map(|p| p.value),
+            Target::StepCommand { ref procedure, ref step } => {
+                self.installed_plugin().and_then(|plugin| {
+                    self.server().get_procedure_command(&plugin.plugin_name, procedure, step)
+                })
+            },
+            Target::ProcedureForm { ref procedure } => {
+                self.server().get_property(&form::form_property(self.meta().key(), procedure)).map(|p| p.value)
+            },
+            Target::StepAttribute { ref procedure, ref step, ref attribute } => {
+                self.installed_plugin().and_then(|plugin| {
+                    self.server().get_step(&plugin.plugin_name, procedure, step)
+                }).map(|step| step.attribute(attribute).and_then(|value| value.clone()).unwrap_or_default())
+            },
+            Target::FormalParameter { ref procedure, ref parameter, ref attribute } => {
+                self.installed_plugin().and_then(|plugin| {
+                    self.server().get_formal_parameter(&plugin.plugin_name, procedure, parameter)
+                }).map(|parameter| parameter.attribute(attribute).and_then(|value| value.clone()).unwrap_or_default())
+            },
+//            The server keeps the objects, not the DSL, so there is nothing to compare with
+            Target::ProcedureSteps { .. } | Target::Dsl { .. } => {
+                return Err(Error::Mapping(format!("{} cannot be compared with the server", target)));
+            }
+        };
+        match result {
+            Ok(value) => Ok(Some(value)),
+            Err(ref e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
 
     fn get_file_content(&self, path: &Path, meta: &PluginMeta) -> Result<String, Error> {
         let res = File::open(path);
@@ -90,33 +384,29 @@ pub trait PartialUpdate {
             Ok(file) => f = file,
             Err(e) => {
                 let err = format!("Cannot open {}: {}", path.to_str().unwrap(), e);
-                return Err(Error::new(ErrorKind::Other, err));
+                return Err(Error::Io(io::Error::new(e.kind(), err)));
             }
         };
         let mut contents = String::new();
         f.read_to_string(&mut contents)?;
-        let plugin_name = format!("{}-{}", &meta.key, &meta.version);
-        contents = contents.replace("@PLUGIN_NAME@", &plugin_name);
-        contents = contents.replace("@PLUGIN_VERSION@", &meta.version);
-        contents = contents.replace("@PLUGIN_KEY@", &meta.key);
-        Ok(contents)
+        Ok(expand_placeholders(meta, &contents))
     }
 }
 
-impl PartialUpdate for PluginGradle {
-    type PluginType = PluginGradle;
+impl<S: FlowServer> PartialUpdate for PluginGradle<S> {
+    type PluginType = PluginGradle<S>;
+    type Server = S;
 
-    fn update(&self, path: &PathBuf) -> Result<(), Error> {
+    fn resolve_target(&self, path: &PathBuf) -> Result<Option<Target>, Error> {
         let manifest = self.read_manifest()?;
         let xpath = self.find_xpath(path, &manifest);
-        if xpath.is_some() {
-            let xpath = xpath.unwrap();
-            self.update_by_xpath(&xpath, path)?;
+        match xpath {
+            Some(xpath) => Ok(Some(self.target_by_xpath(&xpath)?)),
+            None => Ok(None)
         }
-        Ok(())
     }
 
-    fn build(folder: &PathBuf, ef_client: EFClient, options: UpdateOptions) -> Result<Self::PluginType, Error> {
+    fn build(folder: &PathBuf, server: S, options: UpdateOptions) -> Result<Self::PluginType, Error> {
         println!("Reading gradle metadata\n");
         let mut gradle_path = folder.clone();
         gradle_path.push("build.gradle");
@@ -145,21 +435,43 @@ impl PartialUpdate for PluginGradle {
         Ok(PluginGradle {
             meta: metadata,
             manifest_path,
-            ef_client,
-            update_options: options
+            server,
+            update_options: options,
+            uploads: UploadCache::default(),
+            sync_state: SyncState::for_plugin(folder),
         })
     }
+
+    fn meta(&self) -> &PluginMeta {
+        &self.meta
+    }
+
+    fn server(&self) -> &S {
+        &self.server
+    }
+
+    fn options(&self) -> &UpdateOptions {
+        &self.update_options
+    }
+
+    fn uploads(&self) -> &UploadCache {
+        &self.uploads
+    }
+
+    fn sync_state(&self) -> &SyncState {
+        &self.sync_state
+    }
 }
 
-impl PluginGradle {
+impl<S: FlowServer> PluginGradle<S> {
     fn read_manifest(&self) -> Result<Manifest, Error> {
         let mut f = File::open(&self.manifest_path)?;
         let mut contents = String::new();
         f.read_to_string(&mut contents)?;
-        let manifest: Result<Manifest, serde_xml_rs::Error> = deserialize(contents.as_bytes());
+        let manifest: Result<Manifest, serde_xml_rs::Error> = from_reader(contents.as_bytes());
         match manifest {
             Ok(m) => Ok(m),
-            Err(e) => Err(Error::new(ErrorKind::Other, format!("Cannot parse manifest: {}", e)))
+            Err(e) => Err(Error::Metadata(format!("Cannot parse manifest: {}", e)))
         }
     }
 
@@ -173,87 +485,79 @@ impl PluginGradle {
         None
     }
 
-    fn update_by_xpath(&self, xpath: &str, file_path: &PathBuf) -> Result<(), Error> {
-        let tokens = xpath.split("/");
-        let mut path = Vec::new();
-        let mut procedure_name: Option<&str> = None;
-        let mut step_name: Option<&str> = None;
-        for token in tokens {
-            if token.starts_with("property") {
-                let re = Regex::new("propertyName=[\"'](.+?)[\"']").unwrap();
-                let caps = re.captures(token);
-                if caps.is_some() {
-                    let property_name = caps.unwrap().get(1).unwrap().as_str();
-                    path.push(property_name);
-                }
-            } else if token.starts_with("propertySheet") {
-                println!("Property sheet");
-            } else if token.starts_with("procedure") {
-                println!("Procedure: {}", token);
-                let re = Regex::new("procedureName=[\"']([\\w\\s]+)[\"']").unwrap();
-                let caps = re.captures(token);
-                if caps.is_some() {
-                    procedure_name = Some(caps.unwrap().get(1).unwrap().as_str());
+    fn target_by_xpath(&self, xpath: &str) -> Result<Target, Error> {
+        match xpath::parse(xpath)? {
+            XPathTarget::Property { ref owner, ref path } => {
+                Ok(Target::Property(format!("{}/{}", owner.property_sheet(&self.meta.key), path.join("/"))))
+            },
+            XPathTarget::StepCommand { procedure, step } => Ok(Target::StepCommand { procedure, step }),
+            XPathTarget::StepAttribute { procedure, step, attribute } => {
+                if Step::default().attribute(&attribute).is_none() {
+                    return Err(Error::Mapping(format!("Step attribute {} of {} is not supported", attribute, xpath)));
                 }
-            } else if token.starts_with("step") {
-                println!("Step: {}", token);
-                let re = Regex::new("stepName=[\"']([\\w\\s]+)[\"']").unwrap();
-                let caps = re.captures(token);
-                if caps.is_some() {
-                    step_name = Some(caps.unwrap().get(1).unwrap().as_str());
+                Ok(Target::StepAttribute { procedure, step, attribute })
+            },
+            XPathTarget::FormalParameter { procedure, parameter, attribute } => {
+                if FormalParameter::default().attribute(&attribute).is_none() {
+                    return Err(Error::Mapping(format!("Formal parameter attribute {} of {} is not supported", attribute, xpath)));
                 }
-            }
-        }
-        let value = &self.get_file_content(file_path, &self.meta)?;
-        let plugin_name = &self.meta.key;
-        if procedure_name == None {
-            let property_name = format!("/plugins/{}/project/{}", plugin_name, path.join("/"));
-            println!("Property name: {}", property_name);
-            self.ef_client.set_property(&property_name, &value)?;
-            return Ok(());
-        } else {
-            if step_name == None {
-                let property_name = format!("/plugins/{}/project/procedures/{}/{}", plugin_name, procedure_name.unwrap(), path.join("/"));
-                println!("Property name: {}", property_name);
-                self.ef_client.set_property(&property_name, &value)?;
-                return Ok(());
-            } else {
-                let procedure_name = procedure_name.expect("procedure name is not found");
-                let step_name = step_name.expect("step name is not found");
-                let plugin = self.ef_client.get_plugin(&self.meta.key)?;
-                println!("Procedure name: {}, step name: {}", procedure_name, step_name);
-                let _res = self.ef_client.set_procedure_command(&plugin.plugin_name, &procedure_name, &step_name, &value)?;
-                println!("Updated step");
-            }
+                Ok(Target::FormalParameter { procedure, parameter, attribute })
+            },
         }
-        Ok(())
     }
 }
 
-impl PartialUpdate for PluginWizard {
-    type PluginType = PluginWizard;
+impl<S: FlowServer> PartialUpdate for PluginWizard<S> {
+    type PluginType = PluginWizard<S>;
+    type Server = S;
 
-    fn update(&self, path: &PathBuf) -> Result<(), Error> {
+    fn resolve_target(&self, path: &PathBuf) -> Result<Option<Target>, Error> {
         let path_str = path.to_str().unwrap();
+        if self.update_options.eval_dsl {
+            if let Some(file) = self.dsl_script(path) {
+                return Ok(Some(Target::Dsl { file }));
+            }
+        }
         if self.is_property(path_str) {
             println!("{} is a property!", path_str);
-            self.update_property(path)?;
+            let property_name = self.property_name(path)?;
+            return Ok(Some(Target::Property(property_name)));
         } else if self.is_step_code(path_str) {
-            self.update_step(path)?;
+            let (procedure, step) = match self.get_procedure_and_step_name(path) {
+                Ok(names) => names,
+                Err(e) => {
+                    return Err(Error::Mapping(format!("Cannot deduce procedure or step name from {}: {}", path.display(), e)));
+                }
+            };
+            return Ok(Some(Target::StepCommand { procedure, step }));
+        } else if self.is_procedure_dsl(path_str) {
+            let procedure = match self.get_procedure_name(path) {
+                Ok(name) => name,
+                Err(e) => {
+                    return Err(Error::Mapping(format!("Cannot deduce procedure name from {}: {}", path.display(), e)));
+                }
+            };
+            return Ok(Some(Target::ProcedureSteps { procedure }));
         } else if self.is_form_xml(path_str) {
-            println!("{} is a form.xml, pls rebuild", path_str);
+            let procedure = match self.get_procedure_name(path) {
+                Ok(name) => name,
+                Err(e) => {
+                    return Err(Error::Mapping(format!("Cannot deduce procedure name from {}: {}", path.display(), e)));
+                }
+            };
+            return Ok(Some(Target::ProcedureForm { procedure }));
         }
-        Ok(())
+        Ok(None)
     }
 
-    fn build(folder: &PathBuf, ef_client: EFClient, options: UpdateOptions) -> Result<Self::PluginType, Error> {
+    fn build(folder: &PathBuf, server: S, options: UpdateOptions) -> Result<Self::PluginType, Error> {
         let metadata_path = folder.join("META-INF").join("plugin.xml");
         println!("Trying {}", metadata_path.to_str().unwrap());
         let mut f = File::open(&metadata_path)?;
         let mut contents = String::new();
         f.read_to_string(&mut contents)?;
         println!("Contents: {}", contents);
-        let plugin: Result<PluginMETAINF, serde_xml_rs::Error> = deserialize(contents.as_bytes());
+        let plugin: Result<PluginMETAINF, serde_xml_rs::Error> = from_reader(contents.as_bytes());
         if options.keep_extensions {
             println!("Keeping files extensions in the property names");
         }
@@ -266,26 +570,50 @@ impl PartialUpdate for PluginWizard {
                 };
                 Ok(PluginWizard{
                     meta: metadata,
-                    ef_client,
-                    update_options: options
+                    server,
+                    update_options: options,
+                    uploads: UploadCache::default(),
+                    sync_state: SyncState::for_plugin(folder),
                 })
             }
-            Err(error) => Err(Error::new(ErrorKind::Other, format!("Cannot parse {}: {}", metadata_path.display(), error)))
+            Err(error) => Err(Error::Metadata(format!("Cannot parse {}: {}", metadata_path.display(), error)))
         }
     }
 
+    fn meta(&self) -> &PluginMeta {
+        &self.meta
+    }
+
+    fn server(&self) -> &S {
+        &self.server
+    }
+
+    fn options(&self) -> &UpdateOptions {
+        &self.update_options
+    }
+
+    fn uploads(&self) -> &UploadCache {
+        &self.uploads
+    }
+
+    fn sync_state(&self) -> &SyncState {
+        &self.sync_state
+    }
 }
 
-impl PluginWizard {
-    fn update_property(&self, path: &PathBuf) -> Result<(), Error> {
+impl<S: FlowServer> PluginWizard<S> {
+    pub fn set_sync_state(&mut self, state: SyncState) {
+        self.sync_state = state;
+    }
+
+    fn property_name(&self, path: &PathBuf) -> Result<String, Error> {
         if !path.is_absolute() {
-            return Err(Error::new(ErrorKind::Other, "Path should be absolute!"));
+            return Err(Error::Mapping(String::from("Path should be absolute!")));
         }
-        let value = self.get_file_content(path, &self.meta)?;
         let prefix = self.meta.folder.join("dsl").join("properties");
         let path = match path.strip_prefix(&prefix) {
             Err(e) => {
-                return Err(Error::new(ErrorKind::Other, format!("Cannot strip prefix: {}", e)));
+                return Err(Error::Mapping(format!("Cannot strip prefix: {}", e)));
             },
             Ok(path) => path
         };
@@ -299,9 +627,7 @@ impl PluginWizard {
         property_name = String::from(re.replace_all(&property_name, "/"));
         let plugin_name = &self.meta.key;
         property_name = format!("/plugins/{}/project/{}", plugin_name, property_name);
-        println!("Property name: {}", property_name);
-        self.ef_client.set_property(&property_name, &value)?;
-        Ok(())
+        Ok(property_name)
     }
 
     fn is_step_code(&self, path: &str) -> bool {
@@ -311,6 +637,21 @@ impl PluginWizard {
         reg.is_match(path)
     }
 
+//    DSL files and the promote/demote scripts, relative to the plugin folder
+    fn dsl_script(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.meta.folder).ok()?.to_str()?.replace(path::MAIN_SEPARATOR, "/");
+        let re = Regex::new("^dsl/(project\\.dsl|procedures/[^/]+/procedure\\.dsl|promote\\.groovy|demote\\.groovy)$").unwrap();
+        if re.is_match(&relative) {
+            Some(relative)
+        } else {
+            None
+        }
+    }
+
+    fn is_procedure_dsl(&self, path: &str) -> bool {
+        Regex::new("procedure\\.dsl$").unwrap().is_match(path)
+    }
+
     fn is_form_xml(&self, path: &str) -> bool {
         Regex::new("form\\.xml$").unwrap().is_match(path)
     }
@@ -323,23 +664,19 @@ impl PluginWizard {
         reg.is_match(path)
     }
 
-    fn update_step(&self, path: &PathBuf) -> Result<(), Error> {
-        let res = self.get_procedure_and_step_name(path);
-        match res {
-            Ok((procedure_name, step_name)) => {
-                println!("Procedure name: {}, step name: {}", procedure_name, step_name);
-                let plugin = &self.ef_client.get_plugin(&self.meta.key)?;
-                let command = &self.get_file_content(path,&self.meta)?;
-                &self.ef_client.set_procedure_command(
-                    &plugin.plugin_name,
-                    &procedure_name, &step_name,
-                    &command);
-            },
-            Err(e) => {
-                eprintln!("Cannot deduce procedure or step name from {}: {}", path.display(), e);
-            }
+//    form.xml and procedure.dsl live in the procedure folder
+    fn get_procedure_name(&self, path: &PathBuf) -> Result<String, Error> {
+        let folder = match path.parent() {
+            Some(folder) if folder.parent() == Some(&self.meta.folder.join("dsl").join("procedures")) => folder,
+            _ => return Err(Error::Mapping(String::from("Form is not in a procedure folder"))),
+        };
+        let mut f = File::open(folder.join("procedure.dsl"))?;
+        let mut contents = String::new();
+        f.read_to_string(&mut contents)?;
+        match Self::deduce_procedure_name(&contents) {
+            Some(name) => Ok(name),
+            None => Err(Error::Mapping(String::from("Cannot deduce procedure name")))
         }
-        Ok(())
     }
 
     pub fn get_procedure_and_step_name(&self, path: &PathBuf) -> Result<(String, String), Error> {
@@ -347,13 +684,13 @@ impl PluginWizard {
         let relative_path = path.strip_prefix(&self.meta.folder);
         let path = path.strip_prefix(&path_part);
         if path.is_err() || relative_path.is_err() {
-            return Err(Error::new(ErrorKind::Other, "Cannot strip prefix"));
+            return Err(Error::Mapping(String::from("Cannot strip prefix")));
         }
         let relative_path = relative_path.unwrap();
         let path = path.unwrap();
         let procedure_folder_name = path.iter().next();
         if procedure_folder_name .is_none() {
-            return Err(Error::new(ErrorKind::Other, "Cannot get procedure folder name"));
+            return Err(Error::Mapping(String::from("Cannot get procedure folder name")));
         }
         let procedure_folder_name = procedure_folder_name.unwrap();
         let procedure_dsl_path = self.meta.folder.join("dsl/procedures").join(procedure_folder_name).join("procedure.dsl");
@@ -363,17 +700,17 @@ impl PluginWizard {
         f.read_to_string(&mut contents)?;
         let file_name = relative_path.file_name();
         if file_name.is_none() {
-            return Err(Error::new(ErrorKind::Other, "Relative path cannot be calculated"));
+            return Err(Error::Mapping(String::from("Relative path cannot be calculated")));
         }
         let step_name = Self::deduce_step_name(&contents, file_name.unwrap().to_str().unwrap());
         if step_name.is_none() {
-            return Err(Error::new(ErrorKind::Other, "Cannot deduce step name"));
+            return Err(Error::Mapping(String::from("Cannot deduce step name")));
         }
         let step_name = step_name.unwrap();
 
         let procedure_name = Self::deduce_procedure_name(&contents);
         if procedure_name.is_none() {
-            return Err(Error::new(ErrorKind::Other, "Cannot deduce procedure name"));
+            return Err(Error::Mapping(String::from("Cannot deduce procedure name")));
         }
         let procedure_name = procedure_name.unwrap();
 
@@ -440,6 +777,92 @@ impl PluginWizard {
 }
 
 
+fn placeholders(meta: &PluginMeta) -> [(&'static str, String); 3] {
+    [
+        ("@PLUGIN_NAME@", format!("{}-{}", &meta.key, &meta.version)),
+        ("@PLUGIN_VERSION@", meta.version.clone()),
+        ("@PLUGIN_KEY@", meta.key.clone()),
+    ]
+}
+
+pub fn expand_placeholders(meta: &PluginMeta, template: &str) -> String {
+    let mut contents = String::from(template);
+    for &(placeholder, ref value) in placeholders(meta).iter() {
+        contents = contents.replace(placeholder, value);
+    }
+    contents
+}
+
+//    Template line split into literal text and placeholders, each with the text it expands to
+fn template_tokens(meta: &PluginMeta, line: &str) -> Vec<(String, String)> {
+    let placeholders = placeholders(meta);
+    let mut tokens = Vec::new();
+    let mut rest = line;
+    loop {
+        let next = placeholders.iter()
+            .filter_map(|&(placeholder, ref value)| rest.find(placeholder).map(|i| (i, placeholder, value)))
+            .min_by_key(|&(i, _, _)| i);
+        match next {
+            Some((i, placeholder, value)) => {
+                if i > 0 {
+                    tokens.push((String::from(&rest[..i]), String::from(&rest[..i])));
+                }
+                tokens.push((String::from(placeholder), value.clone()));
+                rest = &rest[i + placeholder.len()..];
+            },
+            None => {
+                if !rest.is_empty() {
+                    tokens.push((String::from(rest), String::from(rest)));
+                }
+                return tokens;
+            }
+        }
+    }
+}
+
+//    Placeholders are restored only where the text around them is still the same as in the template
+fn restore_line(meta: &PluginMeta, template: &str, value: &str) -> String {
+    let tokens = template_tokens(meta, template);
+    let (mut start, mut left) = (0, 0);
+    while left < tokens.len() && value[start..].starts_with(tokens[left].1.as_str()) {
+        start += tokens[left].1.len();
+        left += 1;
+    }
+    let (mut end, mut right) = (value.len(), tokens.len());
+    while right > left && value[start..end].ends_with(tokens[right - 1].1.as_str()) {
+        end -= tokens[right - 1].1.len();
+        right -= 1;
+    }
+    let mut line = String::new();
+    for token in tokens[..left].iter() {
+        line.push_str(&token.0);
+    }
+    line.push_str(&value[start..end]);
+    for token in tokens[right..].iter() {
+        line.push_str(&token.0);
+    }
+    line
+}
+
+//    Reverses get_file_content for a server value. The value is aligned with the expanded template
+//    line by line, and placeholders are restored only on the lines of the template that have them
+pub fn restore_placeholders(meta: &PluginMeta, template: &str, value: &str) -> String {
+    let template_lines: Vec<&str> = template.split('\n').collect();
+    let expanded: Vec<String> = template_lines.iter().map(|line| expand_placeholders(meta, line)).collect();
+    let expanded: Vec<&str> = expanded.iter().map(|line| line.as_str()).collect();
+    let value_lines: Vec<&str> = value.split('\n').collect();
+
+    let mut restored = Vec::new();
+    for (old, new) in diff::pair_lines(&expanded, &value_lines) {
+        match (old, new) {
+            (Some(i), Some(j)) => restored.push(restore_line(meta, template_lines[i], value_lines[j])),
+            (None, Some(j)) => restored.push(String::from(value_lines[j])),
+            (_, None) => {},
+        }
+    }
+    restored.join("\n")
+}
+
 pub fn guess_plugin_type(path: &PathBuf) -> Result<PluginType, Error> {
     let plugin_manifest = path.join("META-INF").join("plugin.xml");
     if plugin_manifest.exists() {
@@ -449,7 +872,86 @@ pub fn guess_plugin_type(path: &PathBuf) -> Result<PluginType, Error> {
     if build_gradle.exists() {
         return Ok(PluginType::Gradle);
     }
-    Err(Error::new(ErrorKind::Other, format!("Cannot determine plugin type: {}", path.display())))
+    Err(Error::Metadata(format!("Cannot determine plugin type: {}", path.display())))
+}
+
+//    A folder that is not a plugin itself is a workspace holding plugins
+pub fn find_plugins(path: &PathBuf) -> Result<Vec<PathBuf>, Error> {
+    if guess_plugin_type(path).is_ok() {
+        return Ok(vec![path.clone()]);
+    }
+    let mut plugins = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let folder = entry?.path();
+        if folder.is_dir() && guess_plugin_type(&folder).is_ok() {
+            plugins.push(folder);
+        }
+    }
+    if plugins.is_empty() {
+        return Err(Error::Metadata(format!("No plugins found in {}", path.display())));
+    }
+    plugins.sort();
+    Ok(plugins)
+}
+
+#[derive(Debug)]
+pub enum DetectedPlugin<S: FlowServer = EFClient> {
+    Wizard(PluginWizard<S>),
+    Gradle(PluginGradle<S>),
+}
+
+impl<S: FlowServer> PartialUpdate for DetectedPlugin<S> {
+    type PluginType = DetectedPlugin<S>;
+    type Server = S;
+
+    fn build(folder: &PathBuf, server: S, options: UpdateOptions) -> Result<Self::PluginType, Error> {
+        match guess_plugin_type(folder)? {
+            PluginType::PluginWizard => Ok(DetectedPlugin::Wizard(PluginWizard::build(folder, server, options)?)),
+            PluginType::Gradle => Ok(DetectedPlugin::Gradle(PluginGradle::build(folder, server, options)?)),
+        }
+    }
+
+    fn resolve_target(&self, file: &PathBuf) -> Result<Option<Target>, Error> {
+        match *self {
+            DetectedPlugin::Wizard(ref plugin) => plugin.resolve_target(file),
+            DetectedPlugin::Gradle(ref plugin) => plugin.resolve_target(file),
+        }
+    }
+
+    fn meta(&self) -> &PluginMeta {
+        match *self {
+            DetectedPlugin::Wizard(ref plugin) => plugin.meta(),
+            DetectedPlugin::Gradle(ref plugin) => plugin.meta(),
+        }
+    }
+
+    fn server(&self) -> &S {
+        match *self {
+            DetectedPlugin::Wizard(ref plugin) => plugin.server(),
+            DetectedPlugin::Gradle(ref plugin) => plugin.server(),
+        }
+    }
+
+    fn options(&self) -> &UpdateOptions {
+        match *self {
+            DetectedPlugin::Wizard(ref plugin) => plugin.options(),
+            DetectedPlugin::Gradle(ref plugin) => plugin.options(),
+        }
+    }
+
+    fn uploads(&self) -> &UploadCache {
+        match *self {
+            DetectedPlugin::Wizard(ref plugin) => plugin.uploads(),
+            DetectedPlugin::Gradle(ref plugin) => plugin.uploads(),
+        }
+    }
+
+    fn sync_state(&self) -> &SyncState {
+        match *self {
+            DetectedPlugin::Wizard(ref plugin) => plugin.sync_state(),
+            DetectedPlugin::Gradle(ref plugin) => plugin.sync_state(),
+        }
+    }
 }
 
 
@@ -457,35 +959,61 @@ pub fn guess_plugin_type(path: &PathBuf) -> Result<PluginType, Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use server::{Call, RecordingServer};
+
+    fn options() -> UpdateOptions {
+        UpdateOptions {
+            keep_extensions: false,
+            dry_run: false,
+            check_conflicts: false,
+            allow_delete: false,
+            eval_dsl: false,
+        }
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+    }
 
-    fn read_gradle_plugin() -> Result<PluginGradle, Error> {
-        let plugin_path = "/Users/imago/Documents/ecloud/plugins/EC-WebLogic";
-        let ef_client = EFClient::new("ubuntu-esxi", Some("admin"),
-        Some("changeme"), None).unwrap();
+    fn server() -> RecordingServer {
+        let mut server = RecordingServer::new();
+        server.add_plugin("EC-Wizard", "1.2.0");
+        server.add_plugin("EC-Gradle", "2.1.0");
+        server
+    }
+
+    fn read_gradle_plugin() -> Result<PluginGradle<RecordingServer>, Error> {
         let plugin = PluginGradle::build(
-            &PathBuf::from(plugin_path),
-            ef_client
+            &fixture("EC-Gradle"),
+            server(),
+            options()
         );
         plugin
     }
 
+    fn read_wizard_plugin() -> Result<PluginWizard<RecordingServer>, Error> {
+        PluginWizard::build(&fixture("EC-Wizard"), server(), options())
+    }
+
 
     #[test]
     fn read_gradle_metadata() {
         let plugin = read_gradle_plugin();
         assert!(plugin.is_ok());
-        assert_eq!(plugin.unwrap().meta.version, "3.3.0");
+        let plugin = plugin.unwrap();
+        assert_eq!(plugin.meta.version, "2.1.0");
+        assert_eq!(plugin.meta.key, "EC-Gradle");
     }
 
-//    #[test]
-//    fn test_update_file() {
-//        let plugin = read_gradle_plugin();
-//        let plugin = plugin.unwrap();
-//        let file = plugin.meta.folder.join("src").join("main").join("resources").join("project").join("jython").join("add_server_to_cluster.jython");
-//        println!("{:?}", file);
-//        plugin.update(&file);
-//        assert!(true);
-//    }
+    #[test]
+    fn test_update_file() {
+        let plugin = read_gradle_plugin().unwrap();
+        let file = plugin.meta.folder.join("src").join("main").join("resources").join("project").join("jython").join("add_server_to_cluster.jython");
+        let result = plugin.update(&file).unwrap();
+        assert!(match result { UpdateResult::Updated(_) => true, _ => false });
+        let value = plugin.server().property("/plugins/EC-Gradle/project/jython/add_server_to_cluster.jython");
+        assert_eq!(value, Some(String::from("# EC-Gradle 2.1.0\nAdminConfig.save()\n")));
+    }
 
     #[test]
     fn show_xpath() {
@@ -500,21 +1028,149 @@ mod tests {
         assert_eq!(xpath, "//property[propertyName=\"jython\"]/propertySheet/property[propertyName=\"add_server_to_cluster.jython\"]/value");
     }
 
+    #[test]
+    fn gradle_targets() {
+        let plugin = read_gradle_plugin().unwrap();
+        let project = plugin.meta.folder.join("src").join("main").join("resources").join("project");
+
+        let target = plugin.resolve_target(&project.join("ec_setup.pl")).unwrap();
+        assert_eq!(target, Some(Target::Property(String::from("/plugins/EC-Gradle/project/ec_setup"))));
+
+        let target = plugin.resolve_target(&project.join("procedures").join("Deploy").join("form.xml")).unwrap();
+        assert_eq!(target, Some(Target::Property(String::from("/plugins/EC-Gradle/project/procedures/Deploy/ec_parameterForm"))));
+
+        let target = plugin.resolve_target(&project.join("procedures").join("Deploy").join("deploy.pl")).unwrap();
+        assert_eq!(target, Some(Target::StepCommand { procedure: String::from("Deploy"), step: String::from("deploy") }));
+
+        let target = plugin.resolve_target(&plugin.meta.folder.join("build.gradle")).unwrap();
+        assert_eq!(target, None);
+
+        let target = plugin.target_by_xpath("//procedure[procedureName=\"Create-Config.v2\"]/propertySheet/property[propertyName=\"ec_parameterForm\"]/value").unwrap();
+        assert_eq!(target, Target::Property(String::from("/plugins/EC-Gradle/project/procedures/Create-Config.v2/ec_parameterForm")));
+        assert!(plugin.target_by_xpath("//property[propertyName=\"ec_setup\"]").is_err());
+    }
+
+    #[test]
+    fn gradle_step_and_parameter_targets() {
+        let plugin = read_gradle_plugin().unwrap();
+        let step = Step { step_name: String::from("deploy"), ..Step::default() };
+        plugin.server().create_step("EC-Gradle-2.1.0", "Deploy", &step).unwrap();
+        let parameter = FormalParameter { name: String::from("config"), ..FormalParameter::default() };
+        plugin.server().create_formal_parameter("EC-Gradle-2.1.0", "Deploy", &parameter).unwrap();
+
+        let target = plugin.target_by_xpath("//procedure[procedureName=\"Deploy\"]/step[stepName=\"deploy\"]/postProcessor").unwrap();
+        assert_eq!(plugin.fetch(&target).unwrap(), Some(String::new()));
+        plugin.apply(&Mutation { target: target.clone(), value: String::from("postp --loadSnippet") }).unwrap();
+        assert_eq!(plugin.fetch(&target).unwrap(), Some(String::from("postp --loadSnippet")));
+
+        let target = plugin.target_by_xpath("//procedure[procedureName=\"Deploy\"]/formalParameter[formalParameterName=\"config\"]/defaultValue").unwrap();
+        plugin.apply(&Mutation { target: target.clone(), value: String::from("default") }).unwrap();
+        assert_eq!(plugin.server().formal_parameters("EC-Gradle-2.1.0", "Deploy")[0].default_value, Some(String::from("default")));
+
+        let target = plugin.target_by_xpath("//procedure[procedureName=\"Deploy\"]/step[stepName=\"deploy\"]/propertySheet/property[propertyName=\"matcher\"]/value").unwrap();
+        assert_eq!(target, Target::Property(String::from("/plugins/EC-Gradle/project/procedures/Deploy/steps/deploy/matcher")));
+
+        assert!(plugin.target_by_xpath("//procedure[procedureName=\"Deploy\"]/step[stepName=\"deploy\"]/exclusive").is_err());
+    }
+
     #[test]
     fn test_plugin_wizard() {
-        let plugin_path = "/Users/imago/Documents/ecloud/plugins/containers/EC-Kubernetes";
-        let ef_client = EFClient::new("ubuntu-esxi", Some("admin"),
-                                      Some("changeme"), None).unwrap();
-        let plugin = PluginWizard::build(
-            &PathBuf::from(plugin_path),
-            ef_client
-        );
+        let plugin = read_wizard_plugin();
         println!("{:?}", plugin);
 
         assert!(plugin.is_ok());
-//
-//        plugin.unwrap().get_procedure_and_step_name(&PathBuf::from("/Users/imago/Documents/ecloud/plugins/containers/EC-Kubernetes/dsl/procedures/checkCluster/steps/checkCluster.groovy"));
+        let plugin = plugin.unwrap();
+        assert_eq!(plugin.meta.key, "EC-Wizard");
+        assert_eq!(plugin.meta.version, "1.2.0");
+
+        let step = plugin.meta.folder.join("dsl/procedures/Deploy/steps/deploy.groovy");
+        let names = plugin.get_procedure_and_step_name(&step).unwrap();
+        assert_eq!(names, (String::from("Deploy"), String::from("deploy application")));
+    }
+
+    #[test]
+    fn wizard_targets() {
+        let plugin = read_wizard_plugin().unwrap();
+        let folder = plugin.meta.folder.clone();
+
+        let mutation = plugin.resolve(&folder.join("dsl/properties/scripts/Helper.pm")).unwrap().unwrap();
+        assert_eq!(mutation.target, Target::Property(String::from("/plugins/EC-Wizard/project/scripts/Helper")));
+        assert!(mutation.value.contains("our $VERSION = '1.2.0';"));
+        assert!(mutation.value.contains("Hello from EC-Wizard"));
+
+        let mutation = plugin.resolve(&folder.join("dsl/procedures/Deploy/steps/setup.pl")).unwrap().unwrap();
+        assert_eq!(mutation.target, Target::StepCommand { procedure: String::from("Deploy"), step: String::from("setup") });
+        assert!(mutation.value.contains("Preparing EC-Wizard-1.2.0"));
+
+        let target = plugin.resolve_target(&folder.join("dsl/procedures/Deploy/form.xml")).unwrap();
+        assert_eq!(target, Some(Target::ProcedureForm { procedure: String::from("Deploy") }));
 
+        assert_eq!(plugin.resolve_target(&folder.join("README.md")).unwrap(), None);
+    }
+
+    #[test]
+    fn wizard_unknown_procedure() {
+        let plugin = read_wizard_plugin().unwrap();
+        let step = plugin.meta.folder.join("dsl/procedures/Missing/steps/missing.pl");
+        match plugin.resolve_target(&step) {
+            Err(Error::Mapping(_)) => {},
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wizard_keeps_extensions() {
+        let options = UpdateOptions { keep_extensions: true, dry_run: false, check_conflicts: false, allow_delete: false, eval_dsl: false };
+        let plugin = PluginWizard::build(&fixture("EC-Wizard"), server(), options).unwrap();
+        let target = plugin.resolve_target(&plugin.meta.folder.join("dsl/properties/ec_setup.pl")).unwrap();
+        assert_eq!(target, Some(Target::Property(String::from("/plugins/EC-Wizard/project/ec_setup.pl"))));
+    }
+
+    #[test]
+    fn wizard_update_step() {
+        let plugin = read_wizard_plugin().unwrap();
+        let step = plugin.meta.folder.join("dsl/procedures/Deploy/steps/deploy.groovy");
+        plugin.update(&step).unwrap();
+        let command = plugin.server().command("EC-Wizard-1.2.0", "Deploy", "deploy application");
+        assert_eq!(command, Some(String::from("println \"Deploying with EC-Wizard 1.2.0\"\n")));
+    }
+
+    #[test]
+    fn dry_run_does_not_call_server() {
+        let options = UpdateOptions { keep_extensions: false, dry_run: true, check_conflicts: false, allow_delete: false, eval_dsl: false };
+        let plugin = PluginWizard::build(&fixture("EC-Wizard"), server(), options).unwrap();
+        let result = plugin.update(&plugin.meta.folder.join("dsl/properties/ec_setup.pl")).unwrap();
+        match result {
+            UpdateResult::Recorded(mutation) => {
+                assert_eq!(mutation.target, Target::Property(String::from("/plugins/EC-Wizard/project/ec_setup")));
+            },
+            other => panic!("Unexpected result: {:?}", other),
+        }
+        assert!(plugin.server().calls().is_empty());
+    }
+
+    #[test]
+    fn dsl_is_evaluated() {
+        let options = UpdateOptions { eval_dsl: true, ..options() };
+        let plugin = PluginWizard::build(&fixture("EC-Wizard"), server(), options).unwrap();
+        let file = plugin.meta.folder.join("dsl/procedures/Deploy/procedure.dsl");
+        assert_eq!(plugin.resolve_target(&file).unwrap(),
+                   Some(Target::Dsl { file: String::from("dsl/procedures/Deploy/procedure.dsl") }));
+        assert_eq!(plugin.resolve_target(&plugin.meta.folder.join("dsl/properties/ec_setup.pl")).unwrap(),
+                   Some(Target::Property(String::from("/plugins/EC-Wizard/project/ec_setup"))));
+        match plugin.resolve_target(&plugin.meta.folder.join("dsl/properties/scripts/config.dsl")).unwrap() {
+            Some(Target::Property(_)) => {},
+            other => panic!("Unexpected target: {:?}", other),
+        }
+
+        plugin.update(&file).unwrap();
+        match plugin.server().calls().last() {
+            Some(&Call::EvalDsl { ref dsl }) => {
+                assert!(dsl.contains("def pluginName = 'EC-Wizard-1.2.0'"));
+                assert!(!dsl.contains("pluginDir"));
+            },
+            other => panic!("Unexpected call: {:?}", other),
+        }
     }
 
     static FRAGMENT1: &str = "\
@@ -528,7 +1184,7 @@ mod tests {
 
     #[test]
     fn deduce_step_name_test() {
-        let step_name = PluginWizard::deduce_step_name(FRAGMENT1,
+        let step_name = PluginWizard::<RecordingServer>::deduce_step_name(FRAGMENT1,
        "dsl/procedures/procName/steps/anotherStepName.groovy");
         assert!(step_name.is_some());
         assert_eq!(step_name.unwrap(), "another step");
@@ -536,14 +1192,14 @@ mod tests {
 
     #[test]
     fn deduce_procedure_name_test() {
-        let procedure_name = PluginWizard::deduce_procedure_name(FRAGMENT1);
+        let procedure_name = PluginWizard::<RecordingServer>::deduce_procedure_name(FRAGMENT1);
         assert!(procedure_name.is_some());
         assert_eq!(procedure_name.unwrap(), "procName");
     }
 
     #[test]
     fn deduce_procedure_name_in_var_test() {
-        let procedure_name = PluginWizard::deduce_procedure_name(FRAGMENT2);
+        let procedure_name = PluginWizard::<RecordingServer>::deduce_procedure_name(FRAGMENT2);
         assert!(procedure_name.is_some());
         assert_eq!(procedure_name.unwrap(), "procedure");
     }
@@ -556,15 +1212,43 @@ mod tests {
 
     #[test]
     fn test_watch_placeholder() {
-        let plugin_path = "/Users/imago/Documents/ecloud/plugins/containers/EC-Kubernetes";
-        let ef_client = EFClient::new("ubuntu-esxi", Some("admin"),
-                                      Some("changeme"), None).unwrap();
-        let plugin = PluginWizard::build(
-            &PathBuf::from(plugin_path),
-            ef_client
-        ).unwrap();
+        let plugin = read_wizard_plugin().unwrap();
         watch_placeholder(&plugin);
     }
 
 
+
+    #[test]
+    fn restore_plugin_placeholders() {
+        let meta = PluginMeta {
+            key: String::from("EC-Wizard"),
+            version: String::from("1.2.0"),
+            folder: fixture("EC-Wizard"),
+        };
+        let template = "my $name = '@PLUGIN_NAME@';\nmy $key = '@PLUGIN_KEY@';\n";
+        let value = "my $name = 'EC-Wizard-1.2.0';\nmy $key = 'EC-Wizard';\nprint 'fixed';\n";
+        assert_eq!(restore_placeholders(&meta, template, value),
+                   "my $name = '@PLUGIN_NAME@';\nmy $key = '@PLUGIN_KEY@';\nprint 'fixed';\n");
+        assert_eq!(restore_placeholders(&meta, "print 'EC-Wizard 1.2.0';\n", "print 'EC-Wizard 1.2.0 fixed';\n"),
+                   "print 'EC-Wizard 1.2.0 fixed';\n");
+        let template = "version '@PLUGIN_VERSION@'\nrequires 'EC-Base 1.2.0'\nmy $name = '@PLUGIN_NAME@';\n";
+        let value = "version '1.2.0'\nrequires 'EC-Base 1.2.0'\nmy $name = 'EC-Wizard-1.2.0 fixed';\nadded '1.2.0'\n";
+        assert_eq!(restore_placeholders(&meta, template, value),
+                   "version '@PLUGIN_VERSION@'\nrequires 'EC-Base 1.2.0'\nmy $name = '@PLUGIN_NAME@ fixed';\nadded '1.2.0'\n");
+    }
+
+    #[test]
+    fn workspace_plugins() {
+        let workspace = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures");
+        let plugins = find_plugins(&workspace).unwrap();
+        assert_eq!(plugins, vec![fixture("EC-Gradle"), fixture("EC-Wizard")]);
+        assert_eq!(find_plugins(&fixture("EC-Wizard")).unwrap(), vec![fixture("EC-Wizard")]);
+        assert!(find_plugins(&fixture("EC-Wizard").join("dsl")).is_err());
+
+        for folder in plugins {
+            let plugin = DetectedPlugin::build(&folder, server(), options()).unwrap();
+            assert_eq!(plugin.meta().folder(), folder.as_path());
+        }
+    }
+
 }
@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+use std::slice;
+use std::time::Duration;
+use std::sync::mpsc::channel;
+use notify::{self, RecommendedWatcher, Watcher, RecursiveMode, DebouncedEvent};
+use updater::{PartialUpdate, UpdateResult};
+use sync;
+use server::FlowServer;
+use error::Error;
+
+pub fn watch<T>(paths: &[PathBuf], plugins: &[T]) -> notify::Result<()> where T: PartialUpdate {
+    let (tx, rx) = channel();
+    let mut watcher: RecommendedWatcher = Watcher::new(tx, Duration::from_secs(1))?;
+    for path in paths {
+        watcher.watch(path, RecursiveMode::Recursive)?;
+        println!("Started to watch {}", path.to_str().unwrap());
+    }
+    for plugin in plugins {
+        println!("Pushing changes of {} to {}", plugin.meta().key(), plugin.server().name());
+    }
+    loop {
+        match rx.recv() {
+            Ok(event) => dispatch_event(plugins, event),
+            Err(error) => {
+                println!("Watch error: {:?}", error);
+            }
+        }
+    }
+}
+
+pub fn handle_event<T>(plugin: &T, event: DebouncedEvent) where T: PartialUpdate {
+    dispatch_event(slice::from_ref(plugin), event)
+}
+
+//    Editor swap and backup files and the sync state are never mapped
+fn is_ignored(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map_or(false, |name| name.starts_with(".") || name.ends_with("~"))
+}
+
+pub fn dispatch_event<T>(plugins: &[T], event: DebouncedEvent) where T: PartialUpdate {
+    match event {
+        DebouncedEvent::Create(path) | DebouncedEvent::Chmod(path) | DebouncedEvent::Write(path) => {
+            if is_ignored(&path) {
+                return;
+            }
+            println!("Updated or created {}", path.to_str().unwrap());
+            for_owners(plugins, &path, |plugin| plugin.update(&path));
+        },
+        DebouncedEvent::Remove(path) => {
+            if is_ignored(&path) {
+                return;
+            }
+            println!("Removed {}", path.display());
+            for_owners(plugins, &path, |plugin| plugin.remove(&path));
+        },
+//        A rename moves the server object: the old one is deleted and the new one is created
+        DebouncedEvent::Rename(from, to) => {
+            println!("Renamed {} to {}", from.display(), to.display());
+            if !is_ignored(&from) {
+                for_owners(plugins, &from, |plugin| plugin.remove(&from));
+            }
+            if is_ignored(&to) {
+                return;
+            }
+            let files = if to.is_dir() {
+                sync::plugin_files(&to).unwrap_or_default()
+            } else {
+                vec![to]
+            };
+            for file in files {
+                for_owners(plugins, &file, |plugin| plugin.update(&file));
+            }
+        },
+        event => {
+            println!("Other event: {:?}", event);
+        }
+    }
+}
+
+//    Every server gets the change in turn, so a failing server does not stop the others.
+//    Only the plugins whose folder holds the file see the event
+fn for_owners<T, F>(plugins: &[T], path: &Path, action: F) where T: PartialUpdate, F: Fn(&T) -> Result<UpdateResult, Error> {
+    let owners: Vec<&T> = plugins.iter()
+        .filter(|plugin| path.starts_with(plugin.meta().folder()))
+        .collect();
+    if owners.is_empty() {
+        println!("Skipping {}: not in a watched plugin", path.display());
+    }
+    for plugin in owners.iter() {
+        let prefix = if owners.len() > 1 {
+            format!("[{}] ", plugin.server().name())
+        } else {
+            String::new()
+        };
+        match action(plugin) {
+//            The mapping does not depend on the server
+            Err(Error::Mapping(message)) => {
+                println!("Skipping {}: {}", path.display(), message);
+                break;
+            },
+            Err(e) => eprintln!("{}Error while updating: {}", prefix, e),
+            Ok(_) => {
+                if owners.len() > 1 {
+                    println!("{}Done", prefix);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use std::fmt;
+use error::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Owner {
+    Project,
+    Procedure(String),
+    Step { procedure: String, step: String },
+    Schedule(String),
+    WorkflowDefinition(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum XPathTarget {
+    Property { owner: Owner, path: Vec<String> },
+    StepCommand { procedure: String, step: String },
+    StepAttribute { procedure: String, step: String, attribute: String },
+    FormalParameter { procedure: String, parameter: String, attribute: String },
+}
+
+impl Owner {
+//    Property sheet of the object inside the plugin project
+    pub fn property_sheet(&self, plugin_key: &str) -> String {
+        let project = format!("/plugins/{}/project", plugin_key);
+        match *self {
+            Owner::Project => project,
+            Owner::Procedure(ref procedure) => format!("{}/procedures/{}", project, procedure),
+            Owner::Step { ref procedure, ref step } => format!("{}/procedures/{}/steps/{}", project, procedure, step),
+            Owner::Schedule(ref schedule) => format!("{}/schedules/{}", project, schedule),
+            Owner::WorkflowDefinition(ref workflow) => format!("{}/workflowDefinitions/{}", project, workflow),
+        }
+    }
+}
+
+impl fmt::Display for Owner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Owner::Project => write!(f, "project"),
+            Owner::Procedure(ref procedure) => write!(f, "procedure '{}'", procedure),
+            Owner::Step { ref procedure, ref step } => write!(f, "procedure '{}', step '{}'", procedure, step),
+            Owner::Schedule(ref schedule) => write!(f, "schedule '{}'", schedule),
+            Owner::WorkflowDefinition(ref workflow) => write!(f, "workflow definition '{}'", workflow),
+        }
+    }
+}
+
+impl fmt::Display for XPathTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            XPathTarget::Property { ref owner, ref path } => write!(f, "property {} of {}", path.join("/"), owner),
+            XPathTarget::StepCommand { ref procedure, ref step } => write!(f, "command of procedure '{}', step '{}'", procedure, step),
+            XPathTarget::StepAttribute { ref procedure, ref step, ref attribute } =>
+                write!(f, "{} of procedure '{}', step '{}'", attribute, procedure, step),
+            XPathTarget::FormalParameter { ref procedure, ref parameter, ref attribute } =>
+                write!(f, "{} of parameter '{}' of procedure '{}'", attribute, parameter, procedure),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Node {
+    name: String,
+    predicates: Vec<(String, String)>,
+}
+
+impl Node {
+    fn predicate(&self, name: &str) -> Option<&str> {
+        self.predicates.iter().find(|p| p.0 == name).map(|p| p.1.as_str())
+    }
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    xpath: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> Error {
+        Error::Mapping(format!("Unsupported XPath {}: {} at position {}", self.xpath, message, self.position))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, char::is_whitespace) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), Error> {
+        self.skip_whitespace();
+        if self.peek() == Some(expected) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        self.skip_whitespace();
+        let start = self.position;
+        while self.peek().map_or(false, |c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.') {
+            self.position += 1;
+        }
+        if start == self.position || !self.chars[start].is_alphabetic() {
+            self.position = start;
+            return Err(self.error("expected a node name"));
+        }
+        Ok(self.chars[start..self.position].iter().collect())
+    }
+
+    fn literal(&mut self) -> Result<String, Error> {
+        self.skip_whitespace();
+        let quote = match self.peek() {
+            Some(c) if c == '"' || c == '\'' => c,
+            _ => return Err(self.error("expected a quoted value")),
+        };
+        self.position += 1;
+        let start = self.position;
+        while self.peek().map_or(false, |c| c != quote) {
+            self.position += 1;
+        }
+        if self.peek().is_none() {
+            return Err(self.error("unterminated value"));
+        }
+        let value = self.chars[start..self.position].iter().collect();
+        self.position += 1;
+        Ok(value)
+    }
+
+//    Only `[name="value"]` predicates are used in manifests, attributes may be written with @
+    fn node(&mut self) -> Result<Node, Error> {
+        let name = self.name()?;
+        let mut predicates = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('[') {
+                break;
+            }
+            self.position += 1;
+            self.skip_whitespace();
+            if self.peek() == Some('@') {
+                self.position += 1;
+            }
+            let attribute = self.name()?;
+            self.expect('=')?;
+            let value = self.literal()?;
+            self.expect(']')?;
+            predicates.push((attribute, value));
+        }
+        Ok(Node { name, predicates })
+    }
+
+//    `/` and `//` both just separate the nodes, the nodes alone tell where the value belongs
+    fn nodes(&mut self) -> Result<Vec<Node>, Error> {
+        let mut nodes = Vec::new();
+        loop {
+            self.skip_whitespace();
+            while self.peek() == Some('/') {
+                self.position += 1;
+            }
+            if self.peek().is_none() {
+                break;
+            }
+            nodes.push(self.node()?);
+            self.skip_whitespace();
+            match self.peek() {
+                None | Some('/') => {},
+                Some(_) => return Err(self.error("unexpected character")),
+            }
+        }
+        Ok(nodes)
+    }
+}
+
+fn required<'n>(xpath: &str, node: &'n Node, predicate: &str) -> Result<&'n str, Error> {
+    node.predicate(predicate).ok_or_else(|| {
+        Error::Mapping(format!("Unsupported XPath {}: {} needs a {} predicate", xpath, node.name, predicate))
+    })
+}
+
+pub fn parse(xpath: &str) -> Result<XPathTarget, Error> {
+    let mut parser = Parser { chars: xpath.chars().collect(), position: 0, xpath };
+    let nodes = parser.nodes()?;
+    let unsupported = |message: String| Error::Mapping(format!("Unsupported XPath {}: {}", xpath, message));
+
+    let mut owner = Owner::Project;
+    let mut path: Vec<String> = Vec::new();
+    let mut parameter: Option<String> = None;
+    let mut iter = nodes.iter().peekable();
+    while let Some(node) = iter.next() {
+        let last = iter.peek().is_none();
+        match (node.name.as_str(), owner.clone()) {
+            ("project", Owner::Project) if path.is_empty() => {},
+            ("procedure", Owner::Project) if path.is_empty() => {
+                owner = Owner::Procedure(String::from(required(xpath, node, "procedureName")?));
+            },
+            ("schedule", Owner::Project) if path.is_empty() => {
+                owner = Owner::Schedule(String::from(required(xpath, node, "scheduleName")?));
+            },
+            ("workflowDefinition", Owner::Project) if path.is_empty() => {
+                owner = Owner::WorkflowDefinition(String::from(required(xpath, node, "workflowDefinitionName")?));
+            },
+            ("step", Owner::Procedure(ref procedure)) if path.is_empty() && parameter.is_none() => {
+                let step = String::from(required(xpath, node, "stepName")?);
+                owner = Owner::Step { procedure: procedure.clone(), step };
+            },
+            ("formalParameter", Owner::Procedure(_)) if path.is_empty() && parameter.is_none() => {
+                parameter = Some(String::from(required(xpath, node, "formalParameterName")?));
+            },
+            ("propertySheet", _) if parameter.is_none() => {},
+            ("property", _) if parameter.is_none() => {
+                path.push(String::from(required(xpath, node, "propertyName")?));
+            },
+            ("value", _) if last && !path.is_empty() => {
+                return Ok(XPathTarget::Property { owner, path });
+            },
+            (attribute, Owner::Procedure(ref procedure)) if last && parameter.is_some() => {
+                return Ok(XPathTarget::FormalParameter {
+                    procedure: procedure.clone(),
+                    parameter: parameter.unwrap(),
+                    attribute: String::from(attribute),
+                });
+            },
+            ("command", Owner::Step { ref procedure, ref step }) if last && path.is_empty() => {
+                return Ok(XPathTarget::StepCommand { procedure: procedure.clone(), step: step.clone() });
+            },
+            (attribute, Owner::Step { ref procedure, ref step }) if last && path.is_empty() => {
+                return Ok(XPathTarget::StepAttribute {
+                    procedure: procedure.clone(),
+                    step: step.clone(),
+                    attribute: String::from(attribute),
+                });
+            },
+            (name, ref owner) => return Err(unsupported(format!("unexpected {} in {}", name, owner))),
+        }
+    }
+    Err(unsupported(String::from("it does not select a value")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn property(owner: Owner, path: &[&str]) -> XPathTarget {
+        XPathTarget::Property { owner, path: path.iter().map(|p| String::from(*p)).collect() }
+    }
+
+    #[test]
+    fn properties() {
+        assert_eq!(parse("//property[propertyName=\"ec_setup\"]/value").unwrap(),
+                   property(Owner::Project, &["ec_setup"]));
+        assert_eq!(parse("//property[propertyName=\"jython\"]/propertySheet/property[propertyName=\"add_server_to_cluster.jython\"]/value").unwrap(),
+                   property(Owner::Project, &["jython", "add_server_to_cluster.jython"]));
+        assert_eq!(parse("/project/procedure[procedureName='Create-Config.v2']//property[propertyName='ec_parameterForm']/value").unwrap(),
+                   property(Owner::Procedure(String::from("Create-Config.v2")), &["ec_parameterForm"]));
+        assert_eq!(parse("//schedule[scheduleName=\"nightly\"]/propertySheet/property[propertyName=\"hook\"]/value").unwrap(),
+                   property(Owner::Schedule(String::from("nightly")), &["hook"]));
+        assert_eq!(parse("//workflowDefinition[workflowDefinitionName=\"Release\"]/propertySheet/property[propertyName=\"notes\"]/value").unwrap(),
+                   property(Owner::WorkflowDefinition(String::from("Release")), &["notes"]));
+    }
+
+    #[test]
+    fn steps() {
+        let step = Owner::Step { procedure: String::from("Deploy"), step: String::from("run tests") };
+        assert_eq!(parse("//procedure[procedureName=\"Deploy\"]/step[stepName=\"run tests\"]/command").unwrap(),
+                   XPathTarget::StepCommand { procedure: String::from("Deploy"), step: String::from("run tests") });
+        assert_eq!(parse("//procedure[procedureName=\"Deploy\"]/step[stepName=\"run tests\"]/postProcessor").unwrap(),
+                   XPathTarget::StepAttribute {
+                       procedure: String::from("Deploy"),
+                       step: String::from("run tests"),
+                       attribute: String::from("postProcessor"),
+                   });
+        assert_eq!(parse("//procedure[procedureName=\"Deploy\"]/step[stepName=\"run tests\"]/propertySheet/property[propertyName=\"matchers\"]/propertySheet/property[propertyName=\"perl\"]/value").unwrap(),
+                   property(step, &["matchers", "perl"]));
+    }
+
+    #[test]
+    fn formal_parameters() {
+        assert_eq!(parse("//procedure[procedureName=\"Deploy\"]/formalParameter[@formalParameterName='config']/defaultValue").unwrap(),
+                   XPathTarget::FormalParameter {
+                       procedure: String::from("Deploy"),
+                       parameter: String::from("config"),
+                       attribute: String::from("defaultValue"),
+                   });
+    }
+
+    #[test]
+    fn unsupported() {
+        let xpaths = [
+            "//property[propertyName=\"ec_setup\"]",
+            "//property/value",
+            "//property[propertyName=\"ec_setup\"/value",
+            "//property[propertyName=\"ec_setup]/value",
+            "//step[stepName=\"deploy\"]/command",
+            "//procedure[procedureName=\"Deploy\"]/resource[resourceName=\"local\"]/value",
+            "//property[propertyName=\"a\"]/value/text()",
+            "",
+        ];
+        for xpath in xpaths.iter() {
+            match parse(xpath) {
+                Err(Error::Mapping(_)) => {},
+                other => panic!("Unexpected result for {}: {:?}", xpath, other),
+            }
+        }
+    }
+}
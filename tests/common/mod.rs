@@ -0,0 +1,353 @@
+// A tiny stand-in for the Flow REST API, good enough for the calls EFClient makes
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use serde_json::{self, Value};
+use url::percent_encoding::percent_decode;
+
+const API_PREFIX: &str = "/rest/v1.0/";
+
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub raw_path: String,
+    pub query: String,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+impl Request {
+    pub fn json(&self) -> Value {
+        serde_json::from_str(&self.body).unwrap_or(Value::Null)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    requests: Vec<Request>,
+    plugins: HashMap<String, String>,
+    properties: HashMap<String, String>,
+    steps: HashMap<(String, String), Vec<Value>>,
+    parameters: HashMap<(String, String), Vec<Value>>,
+    scripts: Vec<String>,
+    dsl_error: Option<String>,
+    sessions: Vec<String>,
+    require_session: bool,
+}
+
+pub struct StubServer {
+    port: u16,
+    state: Arc<Mutex<State>>,
+}
+
+impl StubServer {
+    pub fn start() -> StubServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Cannot bind stub server");
+        let port = listener.local_addr().unwrap().port();
+        let state = Arc::new(Mutex::new(State::default()));
+        let thread_state = state.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                match stream {
+                    Ok(stream) => handle_connection(stream, &thread_state),
+                    Err(_) => break,
+                }
+            }
+        });
+        StubServer { port, state }
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    pub fn add_plugin(&self, key: &str, version: &str) {
+        self.state.lock().unwrap().plugins.insert(String::from(key), String::from(version));
+    }
+
+    pub fn set_property(&self, name: &str, value: &str) {
+        self.state.lock().unwrap().properties.insert(String::from(name), String::from(value));
+    }
+
+    pub fn property(&self, name: &str) -> Option<String> {
+        self.state.lock().unwrap().properties.get(name).cloned()
+    }
+
+    pub fn step_command(&self, project: &str, procedure: &str, step: &str) -> Option<String> {
+        let key = (String::from(project), String::from(procedure));
+        self.state.lock().unwrap().steps.get(&key)
+            .and_then(|steps| steps.iter().find(|s| s["stepName"] == step).cloned())
+            .map(|s| String::from(s["command"].as_str().unwrap_or("")))
+    }
+
+    pub fn steps(&self, project: &str, procedure: &str) -> Vec<String> {
+        let key = (String::from(project), String::from(procedure));
+        self.state.lock().unwrap().steps.get(&key).map_or(Vec::new(), |steps| {
+            steps.iter().map(|s| String::from(s["stepName"].as_str().unwrap_or(""))).collect()
+        })
+    }
+
+    pub fn formal_parameters(&self, project: &str, procedure: &str) -> Vec<String> {
+        let key = (String::from(project), String::from(procedure));
+        self.state.lock().unwrap().parameters.get(&key).map_or(Vec::new(), |parameters| {
+            parameters.iter().map(|p| String::from(p["formalParameterName"].as_str().unwrap_or(""))).collect()
+        })
+    }
+
+    pub fn scripts(&self) -> Vec<String> {
+        self.state.lock().unwrap().scripts.clone()
+    }
+
+    pub fn fail_dsl(&self, message: &str) {
+        self.state.lock().unwrap().dsl_error = Some(String::from(message));
+    }
+
+    pub fn require_session(&self) {
+        self.state.lock().unwrap().require_session = true;
+    }
+
+    pub fn expire_sessions(&self) {
+        self.state.lock().unwrap().sessions.clear();
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+pub fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join(name)
+}
+
+fn handle_connection(stream: TcpStream, state: &Arc<Mutex<State>>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let request = match read_request(&mut reader) {
+        Some(r) => r,
+        None => return,
+    };
+    let (status, body) = {
+        let mut state = state.lock().unwrap();
+        state.requests.push(request.clone());
+        route(&request, &mut state)
+    };
+    let reason = match status {
+        200 => "OK",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        _ => "Error",
+    };
+    let response = format!("HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                           status, reason, body.len(), body);
+    let mut stream = stream;
+    let _ = stream.write_all(response.as_bytes());
+    let _ = stream.flush();
+}
+
+fn read_request(reader: &mut BufReader<TcpStream>) -> Option<Request> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+    let mut parts = line.trim().split(' ');
+    let method = String::from(parts.next()?);
+    let target = parts.next()?;
+    let (path, query) = match target.find('?') {
+        Some(i) => (&target[..i], &target[i + 1..]),
+        None => (target, ""),
+    };
+
+    let mut headers = HashMap::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).ok()? == 0 {
+            break;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(i) = header.find(':') {
+            headers.insert(header[..i].trim().to_lowercase(), String::from(header[i + 1..].trim()));
+        }
+    }
+
+    let length: usize = headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0);
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).ok()?;
+
+    Some(Request {
+        method,
+        path: percent_decode(path.as_bytes()).decode_utf8_lossy().into_owned(),
+        raw_path: String::from(path),
+        query: String::from(query),
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+fn not_found(what: &str) -> (u16, String) {
+    (404, json!({"error": {"code": "NoSuchObject", "message": format!("{} does not exist", what)}}).to_string())
+}
+
+fn route(request: &Request, state: &mut State) -> (u16, String) {
+//    Anything in front of the API prefix is a context path of a reverse proxy
+    let uri = match request.path.find(API_PREFIX) {
+        Some(i) => &request.path[i + API_PREFIX.len()..],
+        None => return not_found(&request.path),
+    };
+    let method = request.method.as_str();
+
+    if uri == "sessions" && method == "POST" {
+        let sid = format!("stub-session-{}", state.requests.len());
+        state.sessions.push(sid.clone());
+        return (200, json!({"sessionId": sid, "userName": request.json()["userName"]}).to_string());
+    }
+
+    if state.require_session {
+        let sid = request.headers.get("cookie")
+            .and_then(|cookie| cookie.split(';').map(|c| c.trim()).find(|c| c.starts_with("sessionId=")))
+            .map(|c| String::from(&c["sessionId=".len()..]));
+        let valid = match sid {
+            Some(ref sid) => state.sessions.contains(sid),
+            None => false,
+        };
+        if !valid {
+            return (401, json!({"error": {"code": "InvalidSession", "message": "Session is invalid or expired"}}).to_string());
+        }
+    }
+
+    if uri == "server/status" {
+        return (200, json!({"serverStatus": {"serverState": "running", "lastMessage": "Stub server"}}).to_string());
+    }
+
+    if uri == "server/dsl" && method == "POST" {
+        state.scripts.push(String::from(request.json()["dsl"].as_str().unwrap_or("")));
+        return match state.dsl_error {
+            Some(ref message) => (400, json!({"error": {"code": "InvalidScript", "message": message}}).to_string()),
+            None => (200, json!({"value": null}).to_string()),
+        };
+    }
+
+    if uri.starts_with("properties/") {
+        let name = &uri["properties/".len()..];
+        return match method {
+            "PUT" => {
+                let value = String::from(request.json()["value"].as_str().unwrap_or(""));
+                state.properties.insert(String::from(name), value.clone());
+                (200, json!({"property": {"propertyName": name, "value": value}}).to_string())
+            },
+            "DELETE" => match state.properties.remove(name) {
+                Some(value) => (200, json!({"property": {"propertyName": name, "value": value}}).to_string()),
+                None => not_found(name),
+            },
+            _ => match state.properties.get(name) {
+                Some(value) => (200, json!({"property": {"propertyName": name, "value": value}}).to_string()),
+                None => not_found(name),
+            }
+        };
+    }
+
+    if uri.starts_with("plugins/") {
+        let key = &uri["plugins/".len()..];
+        return match state.plugins.get(key) {
+            Some(version) => (200, json!({"plugin": {
+                "pluginName": format!("{}-{}", key, version),
+                "pluginVersion": version
+            }}).to_string()),
+            None => not_found(key),
+        };
+    }
+
+//    Names are decoded one segment at a time, so an encoded slash stays in the name
+    let raw_uri = request.raw_path.find(API_PREFIX).map_or("", |i| &request.raw_path[i + API_PREFIX.len()..]);
+    let decoded: Vec<String> = raw_uri.split('/').map(|s| percent_decode(s.as_bytes()).decode_utf8_lossy().into_owned()).collect();
+    let segments: Vec<&str> = decoded.iter().map(|s| s.as_str()).collect();
+    if segments.len() >= 5 && segments[0] == "projects" && segments[2] == "procedures" && segments[4] == "steps" {
+        let key = (String::from(segments[1]), String::from(segments[3]));
+        let steps = state.steps.entry(key).or_insert_with(Vec::new);
+        if segments.len() == 5 {
+            return match method {
+                "POST" => {
+                    steps.push(request.json());
+                    (200, json!({"step": request.json()}).to_string())
+                },
+                _ => (200, json!({"step": steps.clone()}).to_string()),
+            };
+        }
+        let name = segments[5];
+        let position = steps.iter().position(|s| s["stepName"] == name);
+        return match (method, position) {
+//            Modifying a missing step creates it, so tests can set commands without creating steps first
+            ("PUT", None) => {
+                let mut step = request.json();
+                step["stepName"] = json!(name);
+                steps.push(step.clone());
+                (200, json!({"step": step}).to_string())
+            },
+            ("PUT", Some(index)) => {
+                for (attribute, value) in request.json().as_object().unwrap() {
+                    steps[index][attribute] = value.clone();
+                }
+                (200, json!({"step": steps[index].clone()}).to_string())
+            },
+            ("POST", Some(index)) if request.query.contains("request=moveStep") => {
+                let step = steps.remove(index);
+                let before = request.query.split('&')
+                    .find(|p| p.starts_with("beforeStep="))
+                    .map(|p| percent_decode(p["beforeStep=".len()..].replace('+', " ").as_bytes()).decode_utf8_lossy().into_owned());
+                let target = before.and_then(|b| steps.iter().position(|s| s["stepName"] == b.as_str())).unwrap_or(steps.len());
+                steps.insert(target, step.clone());
+                (200, json!({"step": step}).to_string())
+            },
+            ("DELETE", Some(index)) => {
+                let removed = steps.remove(index);
+                (200, json!({"step": removed}).to_string())
+            },
+            ("GET", Some(index)) => (200, json!({"step": steps[index].clone()}).to_string()),
+            _ => not_found(name),
+        };
+    }
+
+    if segments.len() >= 5 && segments[0] == "projects" && segments[2] == "procedures" && segments[4] == "formalParameters" {
+        let key = (String::from(segments[1]), String::from(segments[3]));
+        let parameters = state.parameters.entry(key).or_insert_with(Vec::new);
+        if segments.len() == 5 {
+            return match method {
+                "POST" => {
+                    parameters.push(request.json());
+                    (200, json!({"formalParameter": request.json()}).to_string())
+                },
+                _ => (200, json!({"formalParameter": parameters.clone()}).to_string()),
+            };
+        }
+        let name = segments[5];
+        let position = parameters.iter().position(|p| p["formalParameterName"] == name);
+        return match (method, position) {
+            ("PUT", Some(index)) => {
+                for (attribute, value) in request.json().as_object().unwrap() {
+                    parameters[index][attribute] = value.clone();
+                }
+                (200, json!({"formalParameter": parameters[index].clone()}).to_string())
+            },
+            ("GET", Some(index)) => (200, json!({"formalParameter": parameters[index].clone()}).to_string()),
+            ("DELETE", Some(index)) => {
+                let removed = parameters.remove(index);
+                (200, json!({"formalParameter": removed}).to_string())
+            },
+            _ => not_found(name),
+        };
+    }
+
+    not_found(uri)
+}
@@ -0,0 +1,228 @@
+extern crate rusty_sentry;
+#[macro_use]
+extern crate serde_json;
+extern crate url;
+
+mod common;
+
+use std::net::TcpListener;
+use std::time::Duration;
+use std::path::PathBuf;
+use rusty_sentry::ef_client::{EFClient, TlsOptions, FormalParameter};
+use rusty_sentry::error::Error;
+use common::StubServer;
+
+fn build_client(stub: &StubServer) -> EFClient {
+    EFClient::new(&stub.url(), Some("admin"),
+                  Some("changeme"), None).unwrap()
+}
+
+#[test]
+fn get_plugin_test() {
+    let stub = StubServer::start();
+    stub.add_plugin("EC-OpenShift", "1.3.0");
+    let client = build_client(&stub);
+    let plugin = client.get_plugin("EC-OpenShift");
+    println!("{:?}", plugin);
+    assert!(plugin.is_ok());
+    let plugin = plugin.unwrap();
+    assert_eq!(plugin.plugin_name, "EC-OpenShift-1.3.0");
+    assert_eq!(plugin.plugin_version, "1.3.0");
+}
+
+#[test]
+fn get_missing_plugin_test() {
+    let stub = StubServer::start();
+    let client = build_client(&stub);
+    let error = client.get_plugin("EC-Missing").unwrap_err();
+    assert!(error.is_not_found());
+}
+
+#[test]
+fn set_procedure_command() {
+    let stub = StubServer::start();
+    stub.add_plugin("EC-OpenShift", "1.3.0");
+    let client = build_client(&stub);
+    let plugin = client.get_plugin("EC-OpenShift").unwrap();
+    let project_name = plugin.plugin_name;
+    let result = client.set_procedure_command(&project_name, "Discover", "discover", "test");
+    assert!(result.is_ok());
+    assert_eq!(stub.step_command("EC-OpenShift-1.3.0", "Discover", "discover"), Some(String::from("test")));
+}
+
+#[test]
+fn get_procedure_command() {
+    let stub = StubServer::start();
+    let client = build_client(&stub);
+    client.set_procedure_command("EC-OpenShift-1.3.0", "Discover", "discover", "test").unwrap();
+    let command = client.get_procedure_command("EC-OpenShift-1.3.0", "Discover", "discover").unwrap();
+    assert_eq!(command, "test");
+    let missing = client.get_procedure_command("EC-OpenShift-1.3.0", "Discover", "missing");
+    assert!(missing.unwrap_err().is_not_found());
+}
+
+#[test]
+fn step_and_parameter_attributes() {
+    let stub = StubServer::start();
+    let client = build_client(&stub);
+    client.set_procedure_command("EC-OpenShift-1.3.0", "Discover", "discover", "test").unwrap();
+    client.set_step_attribute("EC-OpenShift-1.3.0", "Discover", "discover", "postProcessor", "postp").unwrap();
+    let step = client.get_step("EC-OpenShift-1.3.0", "Discover", "discover").unwrap();
+    assert_eq!(step.command, Some(String::from("test")));
+    assert_eq!(step.post_processor, Some(String::from("postp")));
+
+    let parameter = FormalParameter { name: String::from("config"), required: Some(String::from("1")), ..FormalParameter::default() };
+    client.create_formal_parameter("EC-OpenShift-1.3.0", "Discover", &parameter).unwrap();
+    client.set_formal_parameter_attribute("EC-OpenShift-1.3.0", "Discover", "config", "defaultValue", "default").unwrap();
+    let parameter = client.get_formal_parameter("EC-OpenShift-1.3.0", "Discover", "config").unwrap();
+    assert_eq!(parameter.default_value, Some(String::from("default")));
+    assert_eq!(parameter.required, Some(String::from("1")));
+}
+
+#[test]
+fn names_are_encoded() {
+    let stub = StubServer::start();
+    let client = build_client(&stub);
+    client.set_procedure_command("EC-OpenShift-1.3.0", "Deploy app", "build/deploy #1?", "echo 1").unwrap();
+    client.set_procedure_command("EC-OpenShift-1.3.0", "Deploy app", "a & b+c", "echo 2").unwrap();
+    assert_eq!(stub.steps("EC-OpenShift-1.3.0", "Deploy app"), vec!["build/deploy #1?", "a & b+c"]);
+    client.move_step("EC-OpenShift-1.3.0", "Deploy app", "a & b+c", Some("build/deploy #1?")).unwrap();
+    assert_eq!(stub.steps("EC-OpenShift-1.3.0", "Deploy app"), vec!["a & b+c", "build/deploy #1?"]);
+    assert_eq!(client.get_procedure_command("EC-OpenShift-1.3.0", "Deploy app", "build/deploy #1?").unwrap(), "echo 1");
+
+    let parameter = FormalParameter { name: String::from("dir/name?"), ..FormalParameter::default() };
+    client.create_formal_parameter("EC-OpenShift-1.3.0", "Deploy app", &parameter).unwrap();
+    client.set_formal_parameter_attribute("EC-OpenShift-1.3.0", "Deploy app", "dir/name?", "defaultValue", "x").unwrap();
+    let parameter = client.get_formal_parameter("EC-OpenShift-1.3.0", "Deploy app", "dir/name?").unwrap();
+    assert_eq!(parameter.default_value, Some(String::from("x")));
+}
+
+#[test]
+fn set_and_get_property() {
+    let stub = StubServer::start();
+    let client = build_client(&stub);
+    let property = client.set_property("/plugins/EC-OpenShift/project/ec_setup", "setup code").unwrap();
+    assert_eq!(property.value, "setup code");
+    assert_eq!(stub.property("/plugins/EC-OpenShift/project/ec_setup"), Some(String::from("setup code")));
+
+    let property = client.get_property("/plugins/EC-OpenShift/project/ec_setup").unwrap();
+    assert_eq!(property.value, "setup code");
+    assert!(client.get_property("/plugins/EC-OpenShift/project/missing").is_err());
+}
+
+#[test]
+fn invalid_port_is_an_error() {
+    let mut client = EFClient::new("https://flow", None, None, Some("sid")).unwrap();
+    client.set_port("8443").unwrap();
+    assert_eq!(client.base_url(), "https://flow:8443/");
+    match client.set_port("84430") {
+        Err(Error::Config(message)) => assert!(message.contains("84430")),
+        other => panic!("Unexpected result: {:?}", other),
+    }
+    assert_eq!(client.base_url(), "https://flow:8443/");
+}
+
+#[test]
+fn server_status() {
+    let stub = StubServer::start();
+    let client = build_client(&stub);
+    let status = client.status().unwrap();
+    assert_eq!(status.server_state, "running");
+}
+
+#[test]
+fn transport_failure_is_an_error() {
+    let port = {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    };
+    let mut client = EFClient::new(&format!("http://127.0.0.1:{}", port), Some("admin"), Some("changeme"), None).unwrap();
+    client.set_retries(2, Duration::from_millis(1));
+
+    match client.get_property("/server/settings/ipAddress") {
+        Err(Error::Transport(_)) => {},
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn login_test() {
+    let stub = StubServer::start();
+    stub.require_session();
+    let client = build_client(&stub);
+    let sid = client.login().unwrap();
+    assert_eq!(client.session_id(), Some(sid));
+    assert_eq!(client.status().unwrap().server_state, "running");
+}
+
+#[test]
+fn expired_session_is_renewed() {
+    let stub = StubServer::start();
+    stub.require_session();
+    let client = build_client(&stub);
+    client.set_property("/projects/Default/first", "1").unwrap();
+    let first_sid = client.session_id().unwrap();
+
+    stub.expire_sessions();
+    client.set_property("/projects/Default/second", "2").unwrap();
+    assert!(client.session_id().unwrap() != first_sid);
+
+    let logins = stub.requests().iter().filter(|r| r.path == "/rest/v1.0/sessions").count();
+    assert_eq!(logins, 2);
+}
+
+#[test]
+fn invalid_session_without_credentials() {
+    let stub = StubServer::start();
+    stub.require_session();
+    let client = EFClient::new(&stub.url(), None, None, Some("outdated")).unwrap();
+    let error = client.get_plugin("EC-OpenShift").unwrap_err();
+    assert!(error.is_auth());
+}
+
+#[test]
+fn ca_bundle_must_exist() {
+    let mut client = EFClient::new("127.0.0.1", Some("admin"), Some("changeme"), None).unwrap();
+    let tls = TlsOptions {
+        insecure: false,
+        ca_bundle: Some(PathBuf::from("/nonexistent/ca-bundle.pem")),
+    };
+    match client.set_tls(&tls) {
+        Err(Error::Io(_)) => {},
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn ca_bundle_without_certificates() {
+    let mut client = EFClient::new("127.0.0.1", Some("admin"), Some("changeme"), None).unwrap();
+    let tls = TlsOptions {
+        insecure: false,
+        ca_bundle: Some(common::fixture("EC-Wizard").join("README.md")),
+    };
+    assert!(client.set_tls(&tls).is_err());
+}
+
+#[test]
+fn server_urls() {
+    let client = EFClient::new("flow", None, None, Some("sid")).unwrap();
+    assert_eq!(client.base_url(), "https://flow/");
+    let client = EFClient::new("flow:8443", None, None, Some("sid")).unwrap();
+    assert_eq!(client.base_url(), "https://flow:8443/");
+    let client = EFClient::new("http://localhost:8000/commander", None, None, Some("sid")).unwrap();
+    assert_eq!(client.base_url(), "http://localhost:8000/commander/");
+    assert_eq!(client.host(), "localhost");
+
+    match EFClient::new("http://", None, None, Some("sid")) {
+        Err(Error::Config(_)) => {},
+        other => panic!("Unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn context_path() {
+    let stub = StubServer::start();
+    let client = EFClient::new(&format!("{}/flow", stub.url()), Some("admin"), Some("changeme"), None).unwrap();
+    assert_eq!(client.status().unwrap().server_state, "running");
+    assert!(stub.requests().iter().all(|r| r.path.starts_with("/flow/rest/v1.0/")));
+}
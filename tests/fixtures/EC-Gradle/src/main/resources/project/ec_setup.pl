@@ -0,0 +1,2 @@
+my $pluginName = '@PLUGIN_NAME@';
+print "Setting up $pluginName\n";
@@ -0,0 +1 @@
+print "Deploying with @PLUGIN_NAME@\n";
@@ -0,0 +1 @@
+println "Deploying with @PLUGIN_KEY@ @PLUGIN_VERSION@"
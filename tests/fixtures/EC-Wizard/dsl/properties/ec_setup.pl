@@ -0,0 +1,3 @@
+my $pluginName = '@PLUGIN_NAME@';
+my $pluginKey = '@PLUGIN_KEY@';
+print "Setting up $pluginKey ($pluginName)\n";
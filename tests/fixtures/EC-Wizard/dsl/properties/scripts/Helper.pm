@@ -0,0 +1,9 @@
+package Helper;
+
+our $VERSION = '@PLUGIN_VERSION@';
+
+sub greet {
+    return "Hello from @PLUGIN_KEY@";
+}
+
+1;
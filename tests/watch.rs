@@ -0,0 +1,199 @@
+extern crate rusty_sentry;
+extern crate notify;
+#[macro_use]
+extern crate serde_json;
+extern crate url;
+
+mod common;
+
+use notify::DebouncedEvent;
+use rusty_sentry::ef_client::EFClient;
+use rusty_sentry::updater::{PartialUpdate, PluginWizard, PluginGradle, DetectedPlugin, UpdateOptions};
+use rusty_sentry::watcher::{handle_event, dispatch_event};
+use rusty_sentry::sync;
+use rusty_sentry::error::Error;
+use common::{StubServer, fixture};
+
+fn build_client(stub: &StubServer) -> EFClient {
+    EFClient::new(&stub.url(), Some("admin"),
+                  Some("changeme"), None).unwrap()
+}
+
+fn options() -> UpdateOptions {
+    UpdateOptions {
+        keep_extensions: false,
+        dry_run: false,
+        check_conflicts: false,
+        allow_delete: false,
+        eval_dsl: false,
+    }
+}
+
+#[test]
+fn wizard_property_change_is_pushed() {
+    let stub = StubServer::start();
+    let folder = fixture("EC-Wizard");
+    let plugin = PluginWizard::build(&folder, build_client(&stub), options()).unwrap();
+
+    handle_event(&plugin, DebouncedEvent::Write(folder.join("dsl/properties/ec_setup.pl")));
+
+    let value = stub.property("/plugins/EC-Wizard/project/ec_setup").unwrap();
+    assert!(value.contains("my $pluginName = 'EC-Wizard-1.2.0';"));
+    assert!(value.contains("my $pluginKey = 'EC-Wizard';"));
+}
+
+#[test]
+fn wizard_step_change_is_pushed() {
+    let stub = StubServer::start();
+    stub.add_plugin("EC-Wizard", "1.2.0");
+    let folder = fixture("EC-Wizard");
+    let plugin = PluginWizard::build(&folder, build_client(&stub), options()).unwrap();
+
+    handle_event(&plugin, DebouncedEvent::Create(folder.join("dsl/procedures/Deploy/steps/setup.pl")));
+
+    let command = stub.step_command("EC-Wizard-1.2.0", "Deploy", "setup").unwrap();
+    assert!(command.contains("Preparing EC-Wizard-1.2.0"));
+}
+
+#[test]
+fn gradle_change_is_pushed() {
+    let stub = StubServer::start();
+    stub.add_plugin("EC-Gradle", "2.1.0");
+    let folder = fixture("EC-Gradle");
+    let plugin = PluginGradle::build(&folder, build_client(&stub), options()).unwrap();
+    let project = folder.join("src/main/resources/project");
+
+    handle_event(&plugin, DebouncedEvent::Write(project.join("procedures/Deploy/form.xml")));
+    handle_event(&plugin, DebouncedEvent::Write(project.join("procedures/Deploy/deploy.pl")));
+
+    assert!(stub.property("/plugins/EC-Gradle/project/procedures/Deploy/ec_parameterForm").is_some());
+    let command = stub.step_command("EC-Gradle-2.1.0", "Deploy", "deploy").unwrap();
+    assert_eq!(command, "print \"Deploying with EC-Gradle-2.1.0\\n\";\n");
+}
+
+#[test]
+fn unmapped_files_are_ignored() {
+    let stub = StubServer::start();
+    let folder = fixture("EC-Wizard");
+    let plugin = PluginWizard::build(&folder, build_client(&stub), options()).unwrap();
+
+    handle_event(&plugin, DebouncedEvent::Write(folder.join("README.md")));
+    handle_event(&plugin, DebouncedEvent::Remove(folder.join("dsl/properties/ec_setup.pl")));
+
+    assert!(stub.requests().is_empty());
+}
+
+#[test]
+fn push_whole_plugin() {
+    let stub = StubServer::start();
+    stub.add_plugin("EC-Wizard", "1.2.0");
+    let plugin = PluginWizard::build(&fixture("EC-Wizard"), build_client(&stub), options()).unwrap();
+
+    let summary = sync::push(&plugin).unwrap();
+    assert!(summary.failed.is_empty());
+    assert_eq!(summary.updated.len(), 6);
+    assert_eq!(stub.formal_parameters("EC-Wizard-1.2.0", "Deploy"), vec!["config", "dryRun"]);
+    assert_eq!(stub.steps("EC-Wizard-1.2.0", "Deploy"), vec!["setup", "deploy application"]);
+    assert!(stub.property("/plugins/EC-Wizard/project/scripts/Helper").is_some());
+    assert!(stub.step_command("EC-Wizard-1.2.0", "Deploy", "deploy application").is_some());
+    assert_eq!(plugin.meta().key(), "EC-Wizard");
+}
+
+#[test]
+fn change_is_pushed_to_every_server() {
+    let broken = StubServer::start();
+    let stub = StubServer::start();
+    stub.add_plugin("EC-Wizard", "1.2.0");
+    let folder = fixture("EC-Wizard");
+    let plugins = vec![
+        PluginWizard::build(&folder, build_client(&broken), options()).unwrap(),
+        PluginWizard::build(&folder, build_client(&stub), options()).unwrap(),
+    ];
+
+    dispatch_event(&plugins, DebouncedEvent::Write(folder.join("dsl/procedures/Deploy/steps/setup.pl")));
+    dispatch_event(&plugins, DebouncedEvent::Write(folder.join("dsl/properties/ec_setup.pl")));
+
+    assert!(broken.step_command("EC-Wizard-1.2.0", "Deploy", "setup").is_none());
+    assert!(stub.step_command("EC-Wizard-1.2.0", "Deploy", "setup").is_some());
+    assert!(broken.property("/plugins/EC-Wizard/project/ec_setup").is_some());
+    assert!(stub.property("/plugins/EC-Wizard/project/ec_setup").is_some());
+}
+
+#[test]
+fn events_are_routed_to_the_owning_plugin() {
+    let stub = StubServer::start();
+    stub.add_plugin("EC-Wizard", "1.2.0");
+    stub.add_plugin("EC-Gradle", "2.1.0");
+    let wizard = fixture("EC-Wizard");
+    let gradle = fixture("EC-Gradle");
+    let plugins = vec![
+        DetectedPlugin::build(&wizard, build_client(&stub), options()).unwrap(),
+        DetectedPlugin::build(&gradle, build_client(&stub), options()).unwrap(),
+    ];
+
+    dispatch_event(&plugins, DebouncedEvent::Write(wizard.join("dsl/properties/ec_setup.pl")));
+    dispatch_event(&plugins, DebouncedEvent::Write(gradle.join("src/main/resources/project/ec_setup.pl")));
+
+    assert!(stub.property("/plugins/EC-Wizard/project/ec_setup").unwrap().contains("EC-Wizard-1.2.0"));
+    assert!(stub.property("/plugins/EC-Gradle/project/ec_setup").unwrap().contains("EC-Gradle"));
+}
+
+fn delete_options() -> UpdateOptions {
+    UpdateOptions {
+        allow_delete: true,
+        ..options()
+    }
+}
+
+#[test]
+fn deleted_property_is_deleted_on_the_server() {
+    let stub = StubServer::start();
+    stub.set_property("/plugins/EC-Wizard/project/ec_setup", "setup");
+    let folder = fixture("EC-Wizard");
+
+    let plugin = PluginWizard::build(&folder, build_client(&stub), options()).unwrap();
+    handle_event(&plugin, DebouncedEvent::Remove(folder.join("dsl/properties/ec_setup.pl")));
+    assert!(stub.property("/plugins/EC-Wizard/project/ec_setup").is_some());
+
+    let plugin = PluginWizard::build(&folder, build_client(&stub), delete_options()).unwrap();
+    handle_event(&plugin, DebouncedEvent::Remove(folder.join("dsl/properties/ec_setup.pl")));
+    assert!(stub.property("/plugins/EC-Wizard/project/ec_setup").is_none());
+}
+
+#[test]
+fn renamed_property_is_moved() {
+    let stub = StubServer::start();
+    stub.set_property("/plugins/EC-Wizard/project/old_setup", "setup");
+    let folder = fixture("EC-Wizard");
+    let plugin = PluginWizard::build(&folder, build_client(&stub), delete_options()).unwrap();
+
+    handle_event(&plugin, DebouncedEvent::Rename(folder.join("dsl/properties/old_setup.pl"),
+                                                 folder.join("dsl/properties/ec_setup.pl")));
+
+    assert!(stub.property("/plugins/EC-Wizard/project/old_setup").is_none());
+    assert!(stub.property("/plugins/EC-Wizard/project/ec_setup").is_some());
+}
+
+#[test]
+fn dsl_errors_point_at_the_file() {
+    let stub = StubServer::start();
+    stub.add_plugin("EC-Wizard", "1.2.0");
+    let folder = fixture("EC-Wizard");
+    let options = UpdateOptions { eval_dsl: true, ..options() };
+    let plugin = PluginWizard::build(&folder, build_client(&stub), options.clone()).unwrap();
+
+    handle_event(&plugin, DebouncedEvent::Write(folder.join("dsl/procedures/Deploy/procedure.dsl")));
+    assert_eq!(stub.scripts().len(), 1);
+    assert!(stub.scripts()[0].contains("project projectName, {"));
+
+    let plugin = PluginWizard::build(&folder, build_client(&stub), options).unwrap();
+    stub.fail_dsl("startup failed: Script1.groovy: 8: unexpected token: shell @ line 8, column 9.");
+    let error = plugin.update(&folder.join("dsl/procedures/Deploy/procedure.dsl")).unwrap_err();
+    match error {
+        Error::Dsl { ref file, line, .. } => {
+            assert_eq!(file, "dsl/procedures/Deploy/procedure.dsl");
+            assert_eq!(line, Some(7));
+        },
+        other => panic!("Unexpected error: {:?}", other),
+    }
+}